@@ -0,0 +1,34 @@
+use log::info;
+use pewcraft_common::game::GameDefinition;
+use pewcraft_common::server::{websocket, GameServer};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:9000";
+
+fn main() {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let definition_path = args
+        .next()
+        .expect("Usage: pewcraft_server <game definition file> [address]");
+    let address = args.next().unwrap_or_else(|| DEFAULT_ADDRESS.to_owned());
+
+    let file = File::open(&definition_path).expect("Could not open game definition file");
+    let definition: GameDefinition =
+        serde_json::from_reader(BufReader::new(file)).expect("Invalid game definition");
+    definition
+        .check_validity()
+        .expect("Invalid game definition");
+
+    // the definition lives as long as the server i.e. until the process exits
+    let definition: &'static GameDefinition = Box::leak(Box::new(definition));
+    let server = Arc::new(GameServer::new(definition));
+
+    let listener = TcpListener::bind(&address).expect("Could not bind address");
+    info!("Listening on {}", address);
+    websocket::serve(listener, server);
+}
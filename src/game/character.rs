@@ -1,13 +1,16 @@
-use crate::game::class::Class;
-use crate::game::effect::Buff;
+use crate::game::class::{Attribute, Class};
+use crate::game::damage::DamageType;
+use crate::game::effect::{Buff, BuffKind};
 use crate::game::error::Error;
 use crate::game::game_definition::GameDefinition;
 use crate::game::id_map::{Id, IdMap, IdMapBuilder};
 use crate::game::map::Cell;
 use crate::game::map::{GameMap, Team};
+use crate::game::skill::Skill;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CharacterMapBuilder<'a> {
     builder: IdMapBuilder<Character>,
     empty_starting_cells: Vec<(usize, Vec<Id<Cell>>)>,
@@ -33,10 +36,10 @@ impl<'a> CharacterMapBuilder<'a> {
     }
 
     pub fn add(&mut self, c: Character) -> Result<Id<Character>, Error> {
-        let (spots_left, cells_left) = &mut self
+        let (spots_left, cells_left) = self
             .empty_starting_cells
             .get_mut(c.team.raw())
-            .expect("Wrong team id was assigned to the character");
+            .ok_or(Error::InvalidTeam)?;
 
         if self.game_definition.classes.get(c.class).is_none() {
             return Err(Error::InvalidCharacterClass);
@@ -52,14 +55,51 @@ impl<'a> CharacterMapBuilder<'a> {
         }
 
         // Available cells left
-        if !cells_left.remove_item(&c.position).is_some() {
-            return Err(Error::InvalidStartingCell);
-        }
+        match cells_left.iter().position(|cell| *cell == c.position) {
+            Some(i) => cells_left.remove(i),
+            None => return Err(Error::InvalidStartingCell),
+        };
 
         *spots_left -= 1;
         Ok(self.builder.add(c))
     }
 
+    /// Gives back the character's slot and starting cell to its team
+    pub fn remove(&mut self, id: Id<Character>) -> Result<Character, Error> {
+        let c = self.builder.remove(id).ok_or(Error::InvalidCharacterId)?;
+        let (spots_left, cells_left) = self
+            .empty_starting_cells
+            .get_mut(c.team.raw())
+            .expect("Wrong team id was assigned to the character");
+        *spots_left += 1;
+        cells_left.push(c.position);
+        Ok(c)
+    }
+
+    pub fn get(&self, id: Id<Character>) -> Option<&Character> {
+        self.builder.get(id)
+    }
+
+    pub fn characters(&self) -> impl Iterator<Item = (&Id<Character>, &Character)> {
+        self.builder.iter()
+    }
+
+    pub fn num_teams(&self) -> usize {
+        self.empty_starting_cells.len()
+    }
+
+    pub fn spots_left(&self, team: Id<Team>) -> Option<usize> {
+        self.empty_starting_cells
+            .get(team.raw())
+            .map(|(spots_left, _)| *spots_left)
+    }
+
+    pub fn free_starting_cells(&self, team: Id<Team>) -> Option<&[Id<Cell>]> {
+        self.empty_starting_cells
+            .get(team.raw())
+            .map(|(_, cells_left)| cells_left.as_slice())
+    }
+
     pub fn can_build(&self) -> bool {
         for (spots_left, _) in &self.empty_starting_cells {
             if *spots_left > 0 {
@@ -88,7 +128,36 @@ impl<'a> CharacterMapBuilder<'a> {
 
 // TODO: store the applier of the buff's stats here!
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct BuffInstance(Buff, ());
+pub struct BuffInstance {
+    pub buff: Buff,
+    pub caster: Id<Character>,
+    /// Number of turns of the affected character left before the buff expires
+    pub remaining: i32,
+}
+
+impl BuffInstance {
+    pub fn new(buff: Buff, caster: Id<Character>) -> BuffInstance {
+        BuffInstance {
+            remaining: buff.duration,
+            buff,
+            caster,
+        }
+    }
+}
+
+/// How a character has used one of their skills so far
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SkillUsage {
+    /// Rounds left before the skill can be used again
+    pub cooldown: u32,
+    pub uses_this_turn: u32,
+    pub uses: u32,
+    #[serde(default)]
+    pub charges_used: u32,
+    /// Rounds left before the next used charge is recovered
+    #[serde(default)]
+    pub recharge: u32,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Character {
@@ -100,6 +169,18 @@ pub struct Character {
     pub position: Id<Cell>,
     pub buffs: Vec<BuffInstance>,
     pub team: Id<Team>,
+    #[serde(default)]
+    pub skill_usage: BTreeMap<Id<Skill>, SkillUsage>,
+    /// Reactions the character can still use this round
+    #[serde(default)]
+    pub reactions_left: u32,
+
+    /// Character who summoned this one, if any
+    #[serde(default)]
+    pub summoner: Option<Id<Character>>,
+    /// Rounds left before the summon disappears, counting the current one
+    #[serde(default)]
+    pub lifespan: Option<u32>,
 }
 
 impl Character {
@@ -118,7 +199,143 @@ impl Character {
             position,
             buffs: Vec::new(),
             team,
+            skill_usage: BTreeMap::new(),
+            reactions_left: class.reactions_per_round,
+            summoner: None,
+            lifespan: None,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.current_health > 0
+    }
+
+    fn has_buff<F: Fn(&BuffKind) -> bool>(&self, f: F) -> bool {
+        self.buffs.iter().any(|instance| f(&instance.buff.kind))
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.has_buff(|kind| matches!(kind, BuffKind::Stun))
+    }
+
+    pub fn is_rooted(&self) -> bool {
+        self.has_buff(|kind| matches!(kind, BuffKind::Root))
+    }
+
+    pub fn is_silenced(&self) -> bool {
+        self.has_buff(|kind| matches!(kind, BuffKind::Silence))
+    }
+
+    /// Character who taunted this one last, if any
+    pub fn taunted_by(&self) -> Option<Id<Character>> {
+        self.buffs
+            .iter()
+            .rev()
+            .find(|instance| matches!(instance.buff.kind, BuffKind::Taunt))
+            .map(|instance| instance.caster)
+    }
+
+    /// Damage the character's shields can still absorb
+    pub fn shield(&self) -> i32 {
+        self.buffs
+            .iter()
+            .map(|instance| match instance.buff.kind {
+                BuffKind::Shield(amount) => amount,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Consumes the shields, oldest first, and returns how much of the damage they absorbed.
+    /// Depleted shields are removed.
+    pub fn absorb(&mut self, damage: i32) -> i32 {
+        let mut absorbed = 0;
+        for instance in &mut self.buffs {
+            if let BuffKind::Shield(amount) = &mut instance.buff.kind {
+                let soaked = std::cmp::min(*amount, damage - absorbed);
+                *amount -= soaked;
+                absorbed += soaked;
+            }
         }
+        self.buffs.retain(
+            |instance| !matches!(instance.buff.kind, BuffKind::Shield(amount) if amount <= 0),
+        );
+        absorbed
+    }
+
+    /// Called when the character's turn ends: expired buffs are removed
+    pub fn tick_buffs(&mut self) {
+        for instance in &mut self.buffs {
+            instance.remaining -= 1;
+        }
+        self.buffs.retain(|instance| instance.remaining > 0);
+    }
+
+    pub fn can_use_skill(&self, skill_id: Id<Skill>, skill: &Skill) -> Result<(), Error> {
+        let usage = match self.skill_usage.get(&skill_id) {
+            Some(usage) => usage,
+            None => return Ok(()),
+        };
+
+        if usage.cooldown > 0 {
+            return Err(Error::SkillOnCooldown);
+        }
+
+        if skill
+            .uses_per_turn
+            .is_some_and(|uses| usage.uses_this_turn >= uses)
+            || skill.uses_per_game.is_some_and(|uses| usage.uses >= uses)
+        {
+            return Err(Error::SkillUsesExhausted);
+        }
+
+        if skill
+            .charges
+            .is_some_and(|charges| usage.charges_used >= charges)
+        {
+            return Err(Error::NoChargesLeft);
+        }
+
+        Ok(())
+    }
+
+    pub fn use_skill(&mut self, skill_id: Id<Skill>, skill: &Skill) {
+        let usage = self.skill_usage.entry(skill_id).or_default();
+        usage.cooldown = skill.cooldown;
+        usage.uses_this_turn += 1;
+        usage.uses += 1;
+        if skill.charges.is_some() {
+            if usage.charges_used == 0 {
+                usage.recharge = skill.recharge;
+            }
+            usage.charges_used += 1;
+        }
+    }
+
+    /// Called when the character starts playing
+    pub fn start_turn(&mut self) {
+        for usage in self.skill_usage.values_mut() {
+            usage.uses_this_turn = 0;
+        }
+    }
+
+    /// Called when a new round starts
+    pub fn start_round(&mut self, class: &Class, skills: &IdMap<Skill>) {
+        for (skill_id, usage) in &mut self.skill_usage {
+            usage.cooldown = usage.cooldown.saturating_sub(1);
+
+            let recharge = skills.get(*skill_id).map_or(0, |skill| skill.recharge);
+            if usage.charges_used > 0 && recharge > 0 {
+                usage.recharge -= 1;
+                if usage.recharge == 0 {
+                    usage.charges_used -= 1;
+                    if usage.charges_used > 0 {
+                        usage.recharge = recharge;
+                    }
+                }
+            }
+        }
+        self.reactions_left = class.reactions_per_round;
     }
 
     // TODO: look at current debuffs to return the "effective" stats
@@ -159,4 +376,56 @@ impl Character {
     pub fn effective_willpower(&self, class: &Class) -> i32 {
         class.willpower
     }
+
+    pub fn effective_vision(&self, class: &Class) -> i32 {
+        class.vision
+    }
+
+    pub fn effective_action_points(&self, class: &Class) -> i32 {
+        class.action_points
+    }
+
+    pub fn effective_resistance(&self, class: &Class, damage_type: DamageType) -> f32 {
+        let buffs = self
+            .buffs
+            .iter()
+            .map(|instance| match &instance.buff.kind {
+                BuffKind::Resistance(buffed, value) if *buffed == damage_type => *value,
+                _ => 0.0,
+            })
+            .sum::<f32>();
+        class.resistances.get(&damage_type).copied().unwrap_or(0.0) + buffs
+    }
+
+    pub fn effective_element_resistance(&self, class: &Class, element: &str) -> f32 {
+        let buffs = self
+            .buffs
+            .iter()
+            .map(|instance| match &instance.buff.kind {
+                BuffKind::ElementResistance(buffed, value) if buffed == element => *value,
+                _ => 0.0,
+            })
+            .sum::<f32>();
+        class
+            .element_resistances
+            .get(element)
+            .copied()
+            .unwrap_or(0.0)
+            + buffs
+    }
+
+    pub fn effective_attribute(&self, class: &Class, attribute: Attribute) -> i32 {
+        match attribute {
+            Attribute::Health => self.effective_health(class),
+            Attribute::Swiftness => self.effective_swiftness(class),
+            Attribute::Strength => self.effective_strength(class),
+            Attribute::Dexterity => self.effective_dexterity(class),
+            Attribute::Armor => self.effective_armor(class),
+            Attribute::Intelligence => self.effective_intelligence(class),
+            Attribute::Concentration => self.effective_concentration(class),
+            Attribute::Willpower => self.effective_willpower(class),
+            Attribute::Vision => self.effective_vision(class),
+            Attribute::ActionPoints => self.effective_action_points(class),
+        }
+    }
 }
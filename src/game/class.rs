@@ -1,7 +1,11 @@
+use crate::game::damage::DamageType;
+use crate::game::passive::Passive;
+use crate::game::reaction::Reaction;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 // NOTE: we could generate the "Class" struct and the "Attribute" enum jonitly with e.g. a macro
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Attribute {
     Health,
     Swiftness,
@@ -11,6 +15,8 @@ pub enum Attribute {
     Intelligence,
     Concentration,
     Willpower,
+    Vision,
+    ActionPoints,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,7 +27,7 @@ pub struct Class {
     // GENERIC STUFF
     pub health: i32,
     pub mana: i32,
-    pub swiftness: i32, // movement units
+    pub swiftness: i32, // movement units (movement points with the action points turn model)
 
     // PHYSICAL STUFF
     pub strength: i32,  // dmg
@@ -32,4 +38,43 @@ pub struct Class {
     pub intelligence: i32,  // dmg
     pub concentration: i32, // mana (precision for spell is always 100%)
     pub willpower: i32,     // spell defense
+
+    // how far the character can see when playing with fog of war
+    #[serde(default = "Class::default_vision")]
+    pub vision: i32,
+
+    // part of the damage (after armor or willpower) taken less, negative values being
+    // vulnerabilities e.g. -0.5 means 50% more damage
+    #[serde(default)]
+    pub resistances: BTreeMap<DamageType, f32>,
+    // same thing for damage with an element (e.g. "fire"), on top of its type's resistance
+    #[serde(default)]
+    pub element_resistances: BTreeMap<String, f32>,
+
+    // spent on skills with the action points turn model
+    #[serde(default = "Class::default_action_points")]
+    pub action_points: i32,
+
+    #[serde(default)]
+    pub passives: Vec<Passive>,
+
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    // how many reactions can be used between the start of two rounds
+    #[serde(default = "Class::default_reactions_per_round")]
+    pub reactions_per_round: u32,
+}
+
+impl Class {
+    fn default_action_points() -> i32 {
+        1
+    }
+
+    fn default_reactions_per_round() -> u32 {
+        1
+    }
+
+    fn default_vision() -> i32 {
+        6
+    }
 }
@@ -0,0 +1,42 @@
+use crate::game::character::Character;
+use crate::game::damage::DamagePreview;
+use crate::game::id_map::Id;
+use serde::{Deserialize, Serialize};
+
+/// Modifiers of a skill depending on where the attacker and the target stand
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CombatModifiers {
+    /// Positive when attacking from higher ground
+    pub height_difference: i32,
+    /// Added to the precision of the skill
+    pub precision: f32,
+    /// Multiplies every kind of damage
+    pub damage: f32,
+    /// Whether the target is behind cover, which only protects against ranged attacks
+    pub cover: bool,
+    /// Multiplies the physical damage, on top of the other multiplier
+    pub physical_damage: f32,
+}
+
+impl Default for CombatModifiers {
+    fn default() -> Self {
+        CombatModifiers {
+            height_difference: 0,
+            precision: 0.0,
+            damage: 1.0,
+            cover: false,
+            physical_damage: 1.0,
+        }
+    }
+}
+
+/// What using a skill on a cell would do, before any roll
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkillPreview {
+    pub target: Option<Id<Character>>,
+    pub modifiers: CombatModifiers,
+    /// None if the skill always hits
+    pub hit_chance: Option<f32>,
+    /// Direct damage dealt to the target if the skill hits
+    pub damage: DamagePreview,
+}
@@ -1,8 +1,277 @@
 use crate::game::character::Character;
-use crate::game::class::Class;
+use crate::game::class::{Attribute, Class};
+use crate::game::combat::CombatModifiers;
 use crate::game::id_map::IdMap;
+use crate::game::rng::GameRng;
+use crate::game::rules::Rules;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DamageType {
+    Physical, // mitigated by armor
+    Magical,  // mitigated by willpower
+    Pure,     // never mitigated, nor resisted
+}
+
+impl Display for DamageType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DamageType::Physical => "physical",
+            DamageType::Magical => "magical",
+            DamageType::Pure => "pure",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DamagePart {
+    pub raw: i32,
+    /// Flat mitigation (armor or willpower), never more than the raw damage so that it cannot
+    /// heal
+    pub absorbed: i32,
+    /// Percent mitigation, applied after the flat one. Negative for vulnerabilities.
+    #[serde(default)]
+    pub resisted: i32,
+    #[serde(default)]
+    pub critical: bool,
+}
+
+impl DamagePart {
+    /// A resistance of 1 (or more) means immunity
+    fn mitigated(raw: i32, mitigation: i32, resistance: f32, critical: bool) -> DamagePart {
+        let absorbed = mitigation.clamp(0, std::cmp::max(0, raw));
+        let resisted = if raw > absorbed {
+            ((raw - absorbed) as f32 * resistance.min(1.0)) as i32
+        } else {
+            0
+        };
+
+        DamagePart {
+            raw,
+            absorbed,
+            resisted,
+            critical,
+        }
+    }
+
+    pub fn dealt(&self) -> i32 {
+        self.raw - self.absorbed - self.resisted
+    }
+}
+
+impl Add for DamagePart {
+    type Output = DamagePart;
+
+    fn add(self, other: DamagePart) -> DamagePart {
+        DamagePart {
+            raw: self.raw + other.raw,
+            absorbed: self.absorbed + other.absorbed,
+            resisted: self.resisted + other.resisted,
+            critical: self.critical || other.critical,
+        }
+    }
+}
+
+/// Damage dealt by each type, e.g. for the clients to display it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DamageBreakdown {
+    pub physical: DamagePart,
+    pub magical: DamagePart,
+    pub pure: DamagePart,
+}
+
+impl DamageBreakdown {
+    pub fn pure(damage: i32) -> DamageBreakdown {
+        DamageBreakdown {
+            pure: DamagePart {
+                raw: damage,
+                absorbed: 0,
+                resisted: 0,
+                critical: false,
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, damage_type: DamageType) -> &DamagePart {
+        match damage_type {
+            DamageType::Physical => &self.physical,
+            DamageType::Magical => &self.magical,
+            DamageType::Pure => &self.pure,
+        }
+    }
+
+    pub fn parts(&self) -> [(DamageType, &DamagePart); 3] {
+        [
+            (DamageType::Physical, &self.physical),
+            (DamageType::Magical, &self.magical),
+            (DamageType::Pure, &self.pure),
+        ]
+    }
+
+    pub fn absorbed(&self) -> i32 {
+        self.parts().iter().map(|(_, part)| part.absorbed).sum()
+    }
+
+    /// Health lost by the target (negative when healing it)
+    pub fn total(&self) -> i32 {
+        self.parts().iter().map(|(_, part)| part.dealt()).sum()
+    }
+}
+
+impl Add for DamageBreakdown {
+    type Output = DamageBreakdown;
+
+    fn add(self, other: DamageBreakdown) -> DamageBreakdown {
+        DamageBreakdown {
+            physical: self.physical + other.physical,
+            magical: self.magical + other.magical,
+            pure: self.pure + other.pure,
+        }
+    }
+}
+
+impl AddAssign for DamageBreakdown {
+    fn add_assign(&mut self, other: DamageBreakdown) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for DamageBreakdown {
+    fn sum<I: Iterator<Item = DamageBreakdown>>(iter: I) -> DamageBreakdown {
+        iter.fold(DamageBreakdown::default(), |total, damage| total + damage)
+    }
+}
+
+/// Random part of some damage, rolled before computing it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DamageRoll {
+    /// Multiplies the whole damage
+    pub variance: f32,
+    /// Multiplier of the physical part if it is a critical strike
+    pub physical_critical: Option<f32>,
+    pub magical_critical: Option<f32>,
+}
+
+/// No variance nor critical strike
+impl Default for DamageRoll {
+    fn default() -> Self {
+        DamageRoll {
+            variance: 1.0,
+            physical_critical: None,
+            magical_critical: None,
+        }
+    }
+}
+
+/// Range of the damage some attack can deal, before rolling it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct DamagePreview {
+    pub min: DamageBreakdown,
+    pub max: DamageBreakdown,
+    /// Average total
+    pub expected: f32,
+}
+
+impl Add for DamagePreview {
+    type Output = DamagePreview;
+
+    fn add(self, other: DamagePreview) -> DamagePreview {
+        DamagePreview {
+            min: self.min + other.min,
+            max: self.max + other.max,
+            expected: self.expected + other.expected,
+        }
+    }
+}
+
+impl Sum for DamagePreview {
+    fn sum<I: Iterator<Item = DamagePreview>>(iter: I) -> DamagePreview {
+        iter.fold(DamagePreview::default(), |total, damage| total + damage)
+    }
+}
+
+/// e.g. "12 physical (-5 armor) + 8 magical"
+impl Display for DamageBreakdown {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (damage_type, part) in self.parts().iter() {
+            if part.raw == 0 {
+                continue;
+            }
+            if !first {
+                f.write_str(" + ")?;
+            }
+            first = false;
+
+            write!(f, "{} ", part.raw)?;
+            if part.critical {
+                f.write_str("critical ")?;
+            }
+            write!(f, "{}", damage_type)?;
+            if part.absorbed > 0 {
+                let mitigation = match damage_type {
+                    DamageType::Physical => "armor",
+                    _ => "willpower",
+                };
+                write!(f, " (-{} {})", part.absorbed, mitigation)?;
+            }
+            if part.resisted > 0 {
+                write!(f, " (-{} resisted)", part.resisted)?;
+            } else if part.resisted < 0 {
+                write!(f, " (+{} vulnerability)", -part.resisted)?;
+            }
+        }
+
+        if first {
+            f.write_str("0")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a scaling term of some damage is proportional to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingSource {
+    Attacker(Attribute),
+    Target(Attribute),
+    TargetMissingHealth,
+}
+
+impl ScalingSource {
+    pub fn value(
+        self,
+        attacker: &Character,
+        c_att: &Class,
+        defender: &Character,
+        c_def: &Class,
+    ) -> i32 {
+        match self {
+            ScalingSource::Attacker(attribute) => attacker.effective_attribute(c_att, attribute),
+            ScalingSource::Target(attribute) => defender.effective_attribute(c_def, attribute),
+            ScalingSource::TargetMissingHealth => std::cmp::max(
+                0,
+                defender.effective_health(c_def) - defender.current_health,
+            ),
+        }
+    }
+}
+
+/// e.g. 50% of the attacker's intelligence as magical damage
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scaling {
+    pub damage_type: DamageType,
+    pub source: ScalingSource,
+    pub ratio: f32,
+}
+
+/// The flat_* and perc_* fields are shorthands for the most common terms: the percentages are
+/// of the target's max health, and the perc_modif_* ones of the attacker's strength (physical)
+/// and intelligence (magical). Any other term goes in `scaling`.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Damage {
     #[serde(default)]
@@ -23,32 +292,392 @@ pub struct Damage {
     perc_modif_magical: f32,
     #[serde(default)]
     perc_modif_physical: f32,
+
+    #[serde(default)]
+    scaling: Vec<Scaling>,
+
+    /// The damage is multiplied by a random value in [1 - variance, 1 + variance]
+    #[serde(default)]
+    variance: f32,
+
+    /// e.g. "fire", for the target's element resistances to apply as well
+    #[serde(default)]
+    element: Option<String>,
 }
 
 impl Damage {
+    pub fn flat(damage_type: DamageType, amount: i32) -> Damage {
+        let mut damage = Damage::default();
+        match damage_type {
+            DamageType::Physical => damage.flat_physical = amount,
+            DamageType::Magical => damage.flat_magical = amount,
+            DamageType::Pure => damage.flat_pure = amount,
+        }
+        damage
+    }
+
+    /// Only draws from the RNG if the damage has some variance, or if critical strikes are enabled
+    pub fn roll(
+        &self,
+        rules: &Rules,
+        classes: &IdMap<Class>,
+        attacker: &Character,
+        rng: &mut GameRng,
+    ) -> DamageRoll {
+        let c_att = classes.get(attacker.class).expect("Invalid attacker class");
+
+        let variance = if self.variance > 0.0 {
+            1.0 + rng.gen_range(-self.variance, self.variance)
+        } else {
+            1.0
+        };
+
+        let mut critical = |damage_type| {
+            let crits = rules.crits.as_ref()?;
+            if rng.gen::<f32>() < crits.chance(attacker, c_att, damage_type) {
+                Some(crits.multiplier(attacker, c_att, damage_type))
+            } else {
+                None
+            }
+        };
+        let physical_critical = critical(DamageType::Physical);
+        let magical_critical = critical(DamageType::Magical);
+
+        DamageRoll {
+            variance,
+            physical_critical,
+            magical_critical,
+        }
+    }
+
+    pub fn preview(
+        &self,
+        rules: &Rules,
+        classes: &IdMap<Class>,
+        attacker: &Character,
+        defender: &Character,
+        modifiers: &CombatModifiers,
+    ) -> DamagePreview {
+        let c_att = classes.get(attacker.class).expect("Invalid attacker class");
+        let compute = |roll| self.compute_damage(classes, attacker, defender, modifiers, &roll);
+
+        let min = compute(DamageRoll {
+            variance: 1.0 - self.variance,
+            ..Default::default()
+        });
+        let chance = |damage_type| {
+            rules
+                .crits
+                .as_ref()
+                .map_or(0.0, |crits| crits.chance(attacker, c_att, damage_type))
+        };
+        let critical = rules.crits.as_ref().map_or(DamageRoll::default(), |crits| {
+            let multiplier = |damage_type| Some(crits.multiplier(attacker, c_att, damage_type));
+            DamageRoll {
+                physical_critical: multiplier(DamageType::Physical),
+                magical_critical: multiplier(DamageType::Magical),
+                ..Default::default()
+            }
+        });
+        let max = compute(DamageRoll {
+            variance: 1.0 + self.variance,
+            ..critical
+        });
+
+        // the variance is symmetric, so only the critical strikes change the average
+        let average = compute(DamageRoll::default());
+        let average_critical = compute(critical);
+        let expected = average
+            .parts()
+            .iter()
+            .zip(average_critical.parts().iter())
+            .map(|((damage_type, part), (_, critical_part))| {
+                let chance = chance(*damage_type);
+                (1.0 - chance) * part.dealt() as f32 + chance * critical_part.dealt() as f32
+            })
+            .sum();
+
+        DamagePreview { min, max, expected }
+    }
+
     pub fn compute_damage(
         &self,
         classes: &IdMap<Class>,
         attacker: &Character,
         defender: &Character,
-    ) -> i32 {
+        modifiers: &CombatModifiers,
+        roll: &DamageRoll,
+    ) -> DamageBreakdown {
         let c_att = classes.get(attacker.class).expect("Invalid attacker class");
         let c_def = classes.get(defender.class).expect("Invalid attacker class");
 
+        let scaled = |damage_type: DamageType| -> i32 {
+            self.scaling
+                .iter()
+                .filter(|scaling| scaling.damage_type == damage_type)
+                .map(|scaling| {
+                    let value = scaling.source.value(attacker, c_att, defender, c_def);
+                    (scaling.ratio * value as f32) as i32
+                })
+                .sum()
+        };
+
         let physical_raw = self.flat_physical
             + ((self.perc_physical * defender.effective_health(c_def) as f32) as i32)
-            + ((self.perc_modif_physical * attacker.effective_strength(c_att) as f32) as i32);
+            + ((self.perc_modif_physical * attacker.effective_strength(c_att) as f32) as i32)
+            + scaled(DamageType::Physical);
 
         let magical_raw = self.flat_magical
             + ((self.perc_magical * defender.effective_health(c_def) as f32) as i32)
-            + ((self.perc_modif_magical * attacker.effective_strength(c_att) as f32) as i32);
+            + ((self.perc_modif_magical * attacker.effective_intelligence(c_att) as f32) as i32)
+            + scaled(DamageType::Magical);
+
+        let pure_raw = self.flat_pure
+            + ((self.perc_pure * defender.effective_health(c_def) as f32) as i32)
+            + scaled(DamageType::Pure);
+
+        let multiplier = modifiers.damage * roll.variance;
+        let physical_raw = (physical_raw as f32
+            * multiplier
+            * modifiers.physical_damage
+            * roll.physical_critical.unwrap_or(1.0)) as i32;
+        let magical_raw =
+            (magical_raw as f32 * multiplier * roll.magical_critical.unwrap_or(1.0)) as i32;
+        let pure_raw = (pure_raw as f32 * multiplier) as i32;
+
+        let resistance = |damage_type| {
+            let element = self.element.as_ref().map_or(0.0, |element| {
+                defender.effective_element_resistance(c_def, element)
+            });
+            defender.effective_resistance(c_def, damage_type) + element
+        };
+
+        DamageBreakdown {
+            physical: DamagePart::mitigated(
+                physical_raw,
+                defender.effective_armor(c_def),
+                resistance(DamageType::Physical),
+                roll.physical_critical.is_some() && physical_raw > 0,
+            ),
+            magical: DamagePart::mitigated(
+                magical_raw,
+                defender.effective_willpower(c_def),
+                resistance(DamageType::Magical),
+                roll.magical_critical.is_some() && magical_raw > 0,
+            ),
+            pure: DamagePart::mitigated(pure_raw, 0, 0.0, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Damage, DamageBreakdown, DamageRoll, DamageType};
+    use crate::game::character::{BuffInstance, Character};
+    use crate::game::combat::CombatModifiers;
+    use crate::game::effect::{Buff, BuffKind};
+    use crate::game::id_map::Id;
+    use crate::game::rng::GameRng;
+    use crate::game::rules::CritRules;
+    use crate::game::test_utils::game_definition;
+    use serde_json::json;
+
+    #[test]
+    fn test_breakdown() {
+        let mut g = game_definition();
+        let class = g.classes.get_mut(Id::new(0)).unwrap();
+        class.armor = 5;
+        class.willpower = 2;
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let attacker = Character::new(Id::new(0), Id::new(0), class, "attacker", Id::new(0));
+        let defender = Character::new(Id::new(0), Id::new(4), class, "defender", Id::new(1));
+
+        let damage = Damage {
+            flat_physical: 12,
+            flat_magical: 8,
+            ..Default::default()
+        };
+        let breakdown = damage.compute_damage(
+            &g.classes,
+            &attacker,
+            &defender,
+            &CombatModifiers::default(),
+            &DamageRoll::default(),
+        );
+        assert_eq!(breakdown.total(), 7 + 6);
+        assert_eq!(breakdown.absorbed(), 5 + 2);
+        assert_eq!(
+            breakdown.to_string(),
+            "12 physical (-5 armor) + 8 magical (-2 willpower)"
+        );
+
+        // armor higher than the damage does not heal
+        let damage = Damage {
+            flat_physical: 3,
+            ..Default::default()
+        };
+        let breakdown = damage.compute_damage(
+            &g.classes,
+            &attacker,
+            &defender,
+            &CombatModifiers::default(),
+            &DamageRoll::default(),
+        );
+        assert_eq!(breakdown.total(), 0);
+        assert_eq!(breakdown.physical.absorbed, 3);
+
+        assert_eq!(DamageBreakdown::default().to_string(), "0");
+    }
+
+    #[test]
+    fn test_scaling() {
+        let mut g = game_definition();
+        let class = g.classes.get_mut(Id::new(0)).unwrap();
+        class.intelligence = 10;
+        class.armor = 0;
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let attacker = Character::new(Id::new(0), Id::new(0), class, "attacker", Id::new(0));
+        let mut defender = Character::new(Id::new(0), Id::new(4), class, "defender", Id::new(1));
+        defender.current_health = 12;
+
+        // 50% of intelligence + 10% of target max health, and 25% of its missing health
+        let damage: Damage = serde_json::from_value(json!({
+            "scaling": [
+                { "damage_type": "Magical", "source": { "Attacker": "Intelligence" }, "ratio": 0.5 },
+                { "damage_type": "Magical", "source": { "Target": "Health" }, "ratio": 0.1 },
+                { "damage_type": "Physical", "source": "TargetMissingHealth", "ratio": 0.25 }
+            ]
+        }))
+        .unwrap();
+        let breakdown = damage.compute_damage(
+            &g.classes,
+            &attacker,
+            &defender,
+            &CombatModifiers::default(),
+            &DamageRoll::default(),
+        );
+        assert_eq!(breakdown.magical.raw, 5 + 2);
+        assert_eq!(breakdown.physical.raw, 2);
+
+        // the shorthand scales with intelligence, not strength
+        let damage = Damage {
+            perc_modif_magical: 1.0,
+            ..Default::default()
+        };
+        let breakdown = damage.compute_damage(
+            &g.classes,
+            &attacker,
+            &defender,
+            &CombatModifiers::default(),
+            &DamageRoll::default(),
+        );
+        assert_eq!(breakdown.magical.raw, 10);
+    }
+
+    #[test]
+    fn test_crits_and_variance() {
+        let mut g = game_definition();
+        g.rules.crits = Some(CritRules {
+            chance_per_point: 0.1,
+            max_chance: 0.5,
+            base_multiplier: 2.0,
+            multiplier_per_point: 0.0,
+        });
+        let class = g.classes.get_mut(Id::new(0)).unwrap();
+        class.armor = 0;
+        class.dexterity = 3;
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let attacker = Character::new(Id::new(0), Id::new(0), class, "attacker", Id::new(0));
+        let defender = Character::new(Id::new(0), Id::new(4), class, "defender", Id::new(1));
+
+        let damage = Damage {
+            flat_physical: 10,
+            variance: 0.2,
+            ..Default::default()
+        };
+        let preview = damage.preview(
+            &g.rules,
+            &g.classes,
+            &attacker,
+            &defender,
+            &CombatModifiers::default(),
+        );
+        assert_eq!(preview.min.total(), 8);
+        assert_eq!(preview.max.total(), 24);
+        assert!(preview.max.physical.critical);
+        // 30% chance to deal 20 instead of 10
+        assert!((preview.expected - 13.0).abs() < 1e-3);
+
+        let mut rng = GameRng::from_seed(0);
+        let mut crits = 0;
+        for _ in 0..100 {
+            let roll = damage.roll(&g.rules, &g.classes, &attacker, &mut rng);
+            let breakdown = damage.compute_damage(
+                &g.classes,
+                &attacker,
+                &defender,
+                &CombatModifiers::default(),
+                &roll,
+            );
+            assert!(breakdown.total() >= preview.min.total());
+            assert!(breakdown.total() <= preview.max.total());
+            if breakdown.physical.critical {
+                crits += 1;
+            }
+        }
+        assert!(crits > 0 && crits < 100);
+    }
+
+    #[test]
+    fn test_resistances() {
+        let mut g = game_definition();
+        let class = g.classes.get_mut(Id::new(0)).unwrap();
+        class.armor = 2;
+        class.resistances.insert(DamageType::Physical, 0.5);
+        class.element_resistances.insert("fire".to_owned(), -0.5);
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let attacker = Character::new(Id::new(0), Id::new(0), class, "attacker", Id::new(0));
+        let mut defender = Character::new(Id::new(0), Id::new(4), class, "defender", Id::new(1));
+
+        let compute = |damage: &Damage, defender: &Character| {
+            damage.compute_damage(
+                &g.classes,
+                &attacker,
+                defender,
+                &CombatModifiers::default(),
+                &DamageRoll::default(),
+            )
+        };
 
-        let pure_raw =
-            self.flat_pure + ((self.perc_pure * defender.effective_health(c_def) as f32) as i32);
+        // flat mitigation first, then percent: (12 - 2) * 50%
+        let damage = Damage {
+            flat_physical: 12,
+            flat_pure: 3,
+            ..Default::default()
+        };
+        let breakdown = compute(&damage, &defender);
+        assert_eq!(breakdown.physical.resisted, 5);
+        assert_eq!(breakdown.total(), 5 + 3);
+        assert_eq!(
+            breakdown.to_string(),
+            "12 physical (-2 armor) (-5 resisted) + 3 pure"
+        );
 
-        let physical_final = physical_raw - defender.effective_armor(c_def);
-        let magical_final = magical_raw - defender.effective_willpower(c_def);
+        // the fire vulnerability cancels the physical resistance
+        let fire = Damage {
+            element: Some("fire".to_owned()),
+            ..damage.clone()
+        };
+        assert_eq!(compute(&fire, &defender).physical.dealt(), 10);
 
-        physical_final + magical_final + pure_raw
+        let buff = Buff {
+            name: "Stone skin".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 1,
+            kind: BuffKind::Resistance(DamageType::Physical, 0.5),
+        };
+        defender.buffs.push(BuffInstance::new(buff, Id::new(1)));
+        assert_eq!(compute(&damage, &defender).physical.dealt(), 0);
     }
 }
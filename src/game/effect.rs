@@ -1,6 +1,9 @@
+use crate::game::character::Character;
 use crate::game::class::Attribute;
-use crate::game::damage::Damage;
+use crate::game::class::Class;
+use crate::game::damage::{Damage, DamageType, ScalingSource};
 use crate::game::id_map::Id;
+use crate::game::script::Script;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,17 +55,31 @@ pub enum BuffKind {
     DoT(Damage),
     // TODO change that
     Attribute(Attribute, ValueKind),
+    // absorbs the given amount of damage before it reaches the character's health
+    Shield(i32),
+    // added to the character's resistances (negative values for vulnerabilities)
+    Resistance(DamageType, f32),
+    ElementResistance(String, f32),
+    // the character's turns are skipped
+    Stun,
+    // the character cannot move
+    Root,
+    // the character cannot use magical skills
+    Silence,
+    // the character can only use skills on the caster of the buff, or on their allies
+    Taunt,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Buff {
-    name: String,
+    pub name: String,
     /// None means the damage will be applied to the given cell only (i.e. no "explosion" around
     /// the target)
-    range: Option<Range>,
-    success_rate: Option<f32>,
-    duration: i32,
-    kind: BuffKind,
+    pub range: Option<Range>,
+    pub success_rate: Option<f32>,
+    /// Number of turns of the affected character the buff lasts
+    pub duration: i32,
+    pub kind: BuffKind,
     // TODO: add initial stats of attacker somewhere here
 }
 
@@ -74,10 +91,67 @@ pub struct DirectDamage {
     pub damage: Damage,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealScaling {
+    pub source: ScalingSource,
+    pub ratio: f32,
+}
+
+/// Restores health, up to the target's max health
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Heal {
+    #[serde(default)]
+    pub flat: i32,
+    #[serde(default)]
+    pub scaling: Vec<HealScaling>,
+}
+
+impl Heal {
+    pub fn amount(
+        &self,
+        caster: &Character,
+        c_caster: &Class,
+        target: &Character,
+        c_target: &Class,
+    ) -> i32 {
+        let scaled = self
+            .scaling
+            .iter()
+            .map(|scaling| {
+                let value = scaling.source.value(caster, c_caster, target, c_target);
+                (scaling.ratio * value as f32) as i32
+            })
+            .sum::<i32>();
+        std::cmp::max(0, self.flat + scaled)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum EffectKind {
     DirectDamage(DirectDamage),
+    Heal(Heal),
     Buff(Buff),
+    /// Pushes the target away from the caster, by up to the given number of cells
+    Knockback(u32),
+    /// Pulls the target towards the caster, by up to the given number of cells
+    Pull(u32),
+    /// The caster and the target exchange their positions
+    Swap,
+    /// The caster moves to the targeted cell, which must be free
+    Teleport,
+    /// Anything the built-in effects cannot express, see `Script`
+    Script(Script),
+    /// Creates a character of the caster's team on the targeted cell, which must be free
+    Summon(Summon),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Summon {
+    pub class: Id<Class>,
+    pub name: String,
+    /// Rounds the summon lasts, counting the one it was summoned in. None means it stays until it
+    /// dies, or until its summoner dies.
+    pub lifespan: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1,23 +1,61 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter, Result};
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Error {
     // map loading (deserializing)
     InvalidMapSize,
     OverlappingStartingCells,
 
+    // game definition loading
+    InvalidScript(String),
+    InvalidEffect,
+
     // character creation (before game starts)
     TeamFull,
     InvalidCharacterName,
     InvalidStartingCell,
     InvalidCharacterClass,
+    InvalidTeam,
+    InvalidMap,
+    InvalidTeamSize,
+    InvalidCharacterId,
+
+    // server (hosted games and player sessions)
+    InvalidRequest,
+    InvalidGameId,
+    GameAlreadyStarted,
+    GameIdTaken,
+    GameNotStarted,
+    InvalidToken,
+    NotCharacterOwner,
+
+    // saved games and replays
+    UnsupportedSaveVersion,
+    IncompatibleGameDefinition,
+
+    // undo (local games)
+    UndoDisabled,
+    UndoWithFog,
+    NothingToUndo,
+    NothingToRedo,
 
     // player action
     InvalidSkill,
     AlreadyMoved,
     InvalidTarget,
     InvalidRange,
+    NoLineOfSight,
     MoveCellTooFar,
+    CellOccupied,
+    NotEnoughActionPoints,
+    SkillOnCooldown,
+    SkillUsesExhausted,
+    NoChargesLeft,
+    Stunned,
+    Rooted,
+    Silenced,
+    Taunted,
 }
 
 impl Display for Error {
@@ -30,6 +68,11 @@ impl Display for Error {
                 f.write_str("Starting cells cannot have duplicates (different teams or not)")
             }
 
+            Error::InvalidScript(reason) => write!(f, "Effect script is invalid: {}", reason),
+            Error::InvalidEffect => {
+                f.write_str("Effect id does not correspond to an existing effect")
+            }
+
             Error::TeamFull => f.write_str("Team is already full"),
             Error::InvalidStartingCell => {
                 f.write_str("Initial position is either not a starting cell, or is already taken")
@@ -40,12 +83,58 @@ impl Display for Error {
             Error::InvalidCharacterName => {
                 f.write_str("Character's name cannot be made only of whitespaces")
             }
+            Error::InvalidTeam => f.write_str("Team id does not correspond to a team of the map"),
+            Error::InvalidMap => f.write_str("Map id does not correspond to an existing map"),
+            Error::InvalidTeamSize => {
+                f.write_str("Games need at least one team, with at least one character each")
+            }
+            Error::InvalidCharacterId => {
+                f.write_str("Character id does not correspond to an existing character")
+            }
+
+            Error::InvalidRequest => f.write_str("Request could not be parsed"),
+            Error::InvalidGameId => f.write_str("Game id does not correspond to any game"),
+            Error::GameAlreadyStarted => f.write_str("Game has already started"),
+            Error::GameIdTaken => f.write_str("Game id is already used by another game"),
+            Error::GameNotStarted => f.write_str("Game has not started yet"),
+            Error::InvalidToken => f.write_str("Token does not correspond to any player"),
+            Error::NotCharacterOwner => f.write_str("Character is controlled by another player"),
 
-            Error::InvalidSkill => f.write_str("Team is already full"),
+            Error::UnsupportedSaveVersion => {
+                f.write_str("Saved game or replay was created by an unsupported version")
+            }
+            Error::IncompatibleGameDefinition => {
+                f.write_str("Saved game or replay was created with a different game definition")
+            }
+
+            Error::UndoDisabled => f.write_str("Undoing actions is not allowed in this game"),
+            Error::UndoWithFog => {
+                f.write_str("Undoing actions is not allowed with fog of war, as it reveals cells")
+            }
+            Error::NothingToUndo => f.write_str("There is no action which can be undone"),
+            Error::NothingToRedo => f.write_str("There is no action which can be redone"),
+
+            Error::InvalidSkill => f.write_str("Skill id does not correspond to an existing skill"),
             Error::AlreadyMoved => f.write_str("Character has already moved"),
             Error::InvalidTarget => f.write_str("Target is invalid"),
             Error::InvalidRange => f.write_str("Range is invalid"),
+            Error::NoLineOfSight => f.write_str("Target is not in the character's line of sight"),
             Error::MoveCellTooFar => f.write_str("Character cannot move that far"),
+            Error::CellOccupied => f.write_str("Cell is already occupied by a character"),
+            Error::NotEnoughActionPoints => {
+                f.write_str("Character does not have enough action points left")
+            }
+            Error::SkillOnCooldown => f.write_str("Skill is still on cooldown"),
+            Error::SkillUsesExhausted => {
+                f.write_str("Skill cannot be used any more this turn or this game")
+            }
+            Error::NoChargesLeft => f.write_str("Skill has no charges left until one recharges"),
+            Error::Stunned => f.write_str("Character is stunned and can only pass"),
+            Error::Rooted => f.write_str("Character is rooted and cannot move"),
+            Error::Silenced => f.write_str("Character is silenced and cannot use magical skills"),
+            Error::Taunted => {
+                f.write_str("Character is taunted and can only target its taunter or its allies")
+            }
         }
     }
 }
@@ -0,0 +1,75 @@
+use crate::game::character::Character;
+use crate::game::damage::DamageBreakdown;
+use crate::game::id_map::Id;
+use crate::game::map::Cell;
+use crate::game::skill::Skill;
+use serde::{Deserialize, Serialize};
+
+/// Something which happened while resolving an action, for the clients to animate or log it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Moved {
+        character: Id<Character>,
+        from: Id<Cell>,
+        to: Id<Cell>,
+    },
+    /// The source is the character responsible for the damage, if any
+    Damaged {
+        character: Id<Character>,
+        source: Option<Id<Character>>,
+        damage: DamageBreakdown,
+    },
+    /// Part of the damage was absorbed by the character's shields before reaching their health
+    Shielded {
+        character: Id<Character>,
+        absorbed: i32,
+    },
+    /// Overheal is the part of the heal exceeding the character's max health, which was lost
+    Healed {
+        character: Id<Character>,
+        amount: i32,
+        overheal: i32,
+    },
+    /// The character fell into a hole
+    Fell {
+        character: Id<Character>,
+        cell: Id<Cell>,
+    },
+    /// The character used the skill outside of their turn, the skill's effects following
+    Reacted {
+        character: Id<Character>,
+        skill: Id<Skill>,
+    },
+    Summoned {
+        character: Id<Character>,
+        summoner: Id<Character>,
+    },
+    /// The summon disappeared, because it died, its summoner died or its lifespan ended
+    Expired(Id<Character>),
+    /// The skill targeting the character missed
+    Missed(Id<Character>),
+    Died(Id<Character>),
+}
+
+impl Event {
+    /// Every character the event mentions
+    pub fn characters(&self) -> Vec<Id<Character>> {
+        match self {
+            Event::Moved { character, .. }
+            | Event::Shielded { character, .. }
+            | Event::Healed { character, .. }
+            | Event::Fell { character, .. }
+            | Event::Reacted { character, .. }
+            | Event::Expired(character)
+            | Event::Missed(character)
+            | Event::Died(character) => vec![*character],
+            Event::Damaged {
+                character, source, ..
+            } => std::iter::once(*character).chain(*source).collect(),
+            Event::Summoned {
+                character,
+                summoner,
+            } => vec![*character, *summoner],
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use crate::game::class::Class;
-use crate::game::effect::Effect;
+use crate::game::effect::{Effect, EffectKind};
+use crate::game::error::Error;
 use crate::game::id_map::{Id, IdMap};
 use crate::game::map::GameMap;
+use crate::game::rules::Rules;
 use crate::game::skill::Skill;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameDefinition {
@@ -13,6 +16,132 @@ pub struct GameDefinition {
     pub effects: IdMap<Effect>,
     pub maps: IdMap<GameMap>,
 
-    pub class_to_skills: HashMap<Id<Class>, Vec<Id<Skill>>>,
-    pub skill_to_classes: HashMap<Id<Skill>, Vec<Id<Class>>>,
+    pub class_to_skills: BTreeMap<Id<Class>, Vec<Id<Skill>>>,
+    pub skill_to_classes: BTreeMap<Id<Skill>, Vec<Id<Class>>>,
+
+    #[serde(default)]
+    pub rules: Rules,
+}
+
+// FNV-1a, which unlike the std hasher is guaranteed to stay the same across Rust versions
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn strip_cosmetic_fields(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.remove("name");
+            fields.remove("description");
+            fields.values_mut().for_each(strip_cosmetic_fields);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_cosmetic_fields),
+        _ => (),
+    }
+}
+
+impl GameDefinition {
+    /// Checks everything which cannot be checked while deserializing i.e. the maps, summoned
+    /// classes, and the effects and skills referenced by skills, passives and reactions
+    pub fn check_validity(&self) -> Result<(), Error> {
+        for (_, map) in self.maps.iter() {
+            map.check_validity()?;
+        }
+        for (_, effect) in self.effects.iter() {
+            if let EffectKind::Summon(summon) = &effect.kind {
+                if self.classes.get(summon.class).is_none() {
+                    return Err(Error::InvalidCharacterClass);
+                }
+            }
+        }
+
+        let all_effects_exist = self
+            .skills
+            .iter()
+            .all(|(_, skill)| self.has_effects(&skill.effects))
+            && self.classes.iter().all(|(_, class)| {
+                class
+                    .passives
+                    .iter()
+                    .all(|passive| self.has_effects(&passive.effects))
+            });
+        if !all_effects_exist {
+            return Err(Error::InvalidEffect);
+        }
+        for (_, class) in self.classes.iter() {
+            for reaction in &class.reactions {
+                if self.skills.get(reaction.skill).is_none() {
+                    return Err(Error::InvalidSkill);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn has_effects<'a, I: IntoIterator<Item = &'a Id<Effect>>>(&self, effects: I) -> bool {
+        effects
+            .into_iter()
+            .all(|effect| self.effects.get(*effect).is_some())
+    }
+
+    /// Hash of the definition, used to detect that saved games or replays were created with
+    /// different game data. Names and descriptions are left out, as they do not change how the
+    /// game plays. JSON objects keys are sorted when going through a serde_json::Value, which
+    /// makes the result independent of how the definition was built.
+    pub fn fingerprint(&self) -> u64 {
+        let mut value = serde_json::to_value(self).expect("Could not serialize game definition");
+        strip_cosmetic_fields(&mut value);
+        let canonical = value.to_string();
+        canonical.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::error::Error;
+    use crate::game::id_map::Id;
+    use crate::game::test_utils::game_definition;
+    use serde_json::json;
+
+    #[test]
+    fn test_invalid_ids() {
+        let g = game_definition();
+        assert!(matches!(g.check_validity(), Ok(())));
+
+        let mut bad_skill = g.clone();
+        let skill = bad_skill.skills.get_mut(Id::new(0)).unwrap();
+        skill.effects = vec![Id::new(3)].into_iter().collect();
+        assert!(matches!(
+            bad_skill.check_validity(),
+            Err(Error::InvalidEffect)
+        ));
+
+        let mut bad_passive = g.clone();
+        let class = bad_passive.classes.get_mut(Id::new(0)).unwrap();
+        class.passives = serde_json::from_value(json!([{
+            "name": "Thorns",
+            "trigger": "Hit",
+            "target": "Other",
+            "effects": [3]
+        }]))
+        .unwrap();
+        assert!(matches!(
+            bad_passive.check_validity(),
+            Err(Error::InvalidEffect)
+        ));
+
+        let mut bad_reaction = g;
+        let class = bad_reaction.classes.get_mut(Id::new(0)).unwrap();
+        class.reactions = serde_json::from_value(json!([{
+            "trigger": "EnemyLeaves",
+            "target": "Other",
+            "skill": 3
+        }]))
+        .unwrap();
+        assert!(matches!(
+            bad_reaction.check_validity(),
+            Err(Error::InvalidSkill)
+        ));
+    }
 }
@@ -1,19 +1,47 @@
-use crate::game::character::Character;
-use crate::game::effect::{EffectKind, Range, RangeKind, Target};
+use crate::game::character::{BuffInstance, Character};
+use crate::game::combat::{CombatModifiers, SkillPreview};
+use crate::game::damage::{Damage, DamageBreakdown, DamagePreview, DamageRoll};
+use crate::game::effect::{Buff, BuffKind, EffectKind, Range, RangeKind, Target};
 use crate::game::error::Error;
+use crate::game::event::Event;
 use crate::game::game_definition::GameDefinition;
+use crate::game::generational_id_map::GenerationalIdMap;
 use crate::game::id_map::{Id, IdMap};
-use crate::game::map::{Cell, GameMap};
+use crate::game::map::{Cell, GameMap, Team};
+use crate::game::passive::{Passive, PassiveTarget, Trigger};
+use crate::game::reaction::ReactionTrigger;
+use crate::game::rng::GameRng;
+use crate::game::script::{ScriptAction, ScriptContext};
 use crate::game::skill::Skill;
+use crate::game::view::{FogOfWar, GameStateView};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TurnState {
     MoveOrAction,
     ActionOnly,
+    /// Points left to the character playing, with the action points turn model
+    Points {
+        action: i32,
+        movement: i32,
+    },
 }
 
+impl TurnState {
+    /// Whether the character playing cannot do anything else this turn
+    fn is_over(self) -> bool {
+        match self {
+            TurnState::MoveOrAction => false,
+            TurnState::ActionOnly => true,
+            TurnState::Points { action, movement } => action <= 0 && movement <= 0,
+        }
+    }
+}
+
+/// Owner of the passive, and the other character involved in the trigger if there is one
+type Triggered<'g> = (Id<Character>, &'g Passive, Option<Id<Character>>);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Action {
     Movement(Id<Cell>),
@@ -23,33 +51,107 @@ pub enum Action {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameState {
-    pub characters: IdMap<Character>,
+    pub characters: GenerationalIdMap<Character>,
     pub map: Id<GameMap>,
+    /// The character playing is the last one
     pub turn_order: Vec<Id<Character>>,
+    /// Order of the upcoming round, rolled when the current one starts (same layout as turn_order)
+    #[serde(default)]
+    pub next_turn_order: Vec<Id<Character>>,
     pub turn_state: TurnState,
+
+    /// Never sent to the clients, as it would allow them to predict the outcome of their actions
+    #[serde(skip, default = "GameRng::from_entropy")]
+    pub(crate) rng: GameRng,
+    /// What happened during the last action
+    #[serde(skip)]
+    events: Vec<Event>,
 }
 
 impl GameState {
     pub fn new(g: &GameDefinition, characters: IdMap<Character>, map: Id<GameMap>) -> GameState {
+        GameState::with_rng(g, characters, map, GameRng::from_entropy())
+    }
+
+    pub fn with_seed(
+        g: &GameDefinition,
+        characters: IdMap<Character>,
+        map: Id<GameMap>,
+        seed: u64,
+    ) -> GameState {
+        GameState::with_rng(g, characters, map, GameRng::from_seed(seed))
+    }
+
+    fn with_rng(
+        g: &GameDefinition,
+        characters: IdMap<Character>,
+        map: Id<GameMap>,
+        rng: GameRng,
+    ) -> GameState {
         let mut gs = GameState {
-            characters,
+            characters: characters.into(),
             map,
             turn_order: Vec::new(),
+            next_turn_order: Vec::new(),
             turn_state: TurnState::MoveOrAction,
+            rng,
+            events: Vec::new(),
         };
         gs.new_turn(g);
+        gs.start_character_turn(g);
         gs
     }
 
     fn new_turn(&mut self, g: &GameDefinition) {
         assert!(self.turn_order.is_empty());
 
-        let mut turn_order = self.characters.iter().collect::<Vec<_>>();
-        turn_order.sort_by_key(|(_, character)| {
+        if self.next_turn_order.is_empty() {
+            self.next_turn_order = self.roll_turn_order(g);
+        }
+
+        for (_, character) in self.characters.iter_mut() {
             let class = g.classes.get(character.class).expect("Invalid class id");
-            character.effective_swiftness(class)
-        });
-        self.turn_order = turn_order.iter().map(|(id, _)| **id).collect::<Vec<_>>();
+            character.start_round(class, &g.skills);
+            if let Some(lifespan) = &mut character.lifespan {
+                *lifespan = lifespan.saturating_sub(1);
+            }
+        }
+        self.remove_summons();
+
+        let characters = &self.characters;
+        self.turn_order = std::mem::take(&mut self.next_turn_order)
+            .into_iter()
+            .filter(|id| characters.get(*id).is_some_and(Character::is_alive))
+            .collect();
+        self.next_turn_order = self.roll_turn_order(g);
+    }
+
+    fn start_character_turn(&mut self, g: &GameDefinition) {
+        if let Some(id) = self.turn_order.last() {
+            let character = self.characters.get_mut(*id).expect("Invalid character id");
+            character.start_turn();
+            let class = g.classes.get(character.class).expect("Invalid class id");
+            self.turn_state = g.rules.turn_model.start_turn(character, class);
+
+            let mut triggered = Vec::new();
+            self.trigger(g, *id, Trigger::TurnStart, None, &mut triggered);
+            self.apply_passives(g, triggered);
+        }
+    }
+
+    fn roll_turn_order(&mut self, g: &GameDefinition) -> Vec<Id<Character>> {
+        let mut turn_order = g.rules.initiative.roll(g, &self.characters, &mut self.rng);
+        turn_order.reverse();
+        turn_order
+    }
+
+    pub fn view_for_team(
+        &self,
+        g: &GameDefinition,
+        team: Id<Team>,
+        fog: &FogOfWar,
+    ) -> GameStateView {
+        GameStateView::new(self, g, team, fog)
     }
 
     pub fn player_to_play(&self) -> Id<Character> {
@@ -59,102 +161,794 @@ impl GameState {
             .expect("Turn is finished and should be reset")
     }
 
+    /// Events which happened during the last action
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
     pub fn next_action(&mut self, g: &GameDefinition, ga: Action) -> Result<bool, Error> {
+        self.events.clear();
+        let curr_id = self.player_to_play();
         let curr_char = self
             .characters
-            .get(
-                *self
-                    .turn_order
-                    .last()
-                    .expect("Turn is finished and should be reset"),
-            )
+            .get(curr_id)
             .expect("Invalid character id")
             .clone();
 
-        match (ga, self.turn_state) {
-            (Action::Pass, _) => {
-                self.turn_order.pop();
-            }
-            (Action::Skill(skill_id, cell_id), _) => {
-                self.execute_skill(curr_char, g, skill_id, cell_id)?;
-                self.turn_order.pop();
+        let turn_over = match (ga, self.turn_state) {
+            (Action::Pass, _) => true,
+            (_, _) if curr_char.is_stunned() => return Err(Error::Stunned),
+            (Action::Movement(_), _) if curr_char.is_rooted() => return Err(Error::Rooted),
+            (Action::Skill(skill_id, cell_id), turn_state) => {
+                let skill = g.skills.get(skill_id).ok_or(Error::InvalidSkill)?;
+                let turn_state = match turn_state {
+                    TurnState::Points { action, movement } if action >= skill.action_points => {
+                        TurnState::Points {
+                            action: action - skill.action_points,
+                            movement,
+                        }
+                    }
+                    TurnState::Points { .. } => return Err(Error::NotEnoughActionPoints),
+                    // using a skill always ends the turn
+                    _ => TurnState::ActionOnly,
+                };
+
+                self.execute_skill(curr_id, curr_char, g, skill_id, cell_id)?;
+                self.turn_state = turn_state;
+                turn_state.is_over()
             }
+            (Action::Movement(_), TurnState::ActionOnly) => return Err(Error::AlreadyMoved),
             (Action::Movement(cell_id), TurnState::MoveOrAction) => {
-                self.execute_move(curr_char, g, cell_id)?;
+                let class = g.classes.get(curr_char.class).expect("Invalid class id");
+                let swiftness = curr_char.effective_swiftness(class);
+                self.execute_move(curr_id, &curr_char, g, cell_id, swiftness)?;
+                self.turn_state = TurnState::ActionOnly;
+                false
+            }
+            (Action::Movement(cell_id), TurnState::Points { action, movement }) => {
+                let cost = self.execute_move(curr_id, &curr_char, g, cell_id, movement)?;
+                self.turn_state = TurnState::Points {
+                    action,
+                    movement: movement - cost,
+                };
+                self.turn_state.is_over()
+            }
+        };
+
+        let triggered = self.triggered_passives(g, 0);
+        self.apply_passives(g, triggered);
+        self.remove_summons();
+
+        // the character playing can die because of their own action (e.g. by falling into a hole)
+        let turn_over = turn_over
+            || !self
+                .characters
+                .get(curr_id)
+                .is_some_and(Character::is_alive);
+        let characters = &self.characters;
+        self.turn_order
+            .retain(|id| *id == curr_id || characters.get(*id).is_some_and(Character::is_alive));
+
+        Ok(turn_over && self.end_character_turn(g))
+    }
+
+    /// Ends the turn of the character playing, and skips the turns of the stunned characters after
+    /// them. Returns true if a new round started.
+    fn end_character_turn(&mut self, g: &GameDefinition) -> bool {
+        let mut new_round = false;
+        loop {
+            let id = self
+                .turn_order
+                .pop()
+                .expect("Turn is finished and should be reset");
+            let start = self.events.len();
+            self.tick_buffs(g, id);
+            let triggered = self.triggered_passives(g, start);
+            self.apply_passives(g, triggered);
+            self.remove_summons();
+            // the turn is already over: the summons removed with their summoner cannot play next
+            let characters = &self.characters;
+            self.turn_order
+                .retain(|id| characters.get(*id).is_some_and(Character::is_alive));
+
+            if self.turn_order.is_empty() {
+                self.new_turn(g);
+                new_round = true;
+            }
+
+            match self.turn_order.last() {
+                Some(id) if self.characters.get(*id).is_some_and(Character::is_stunned) => continue,
+                _ => break,
             }
-            (Action::Movement(_), TurnState::ActionOnly) => return Err(Error::AlreadyMoved),
         }
 
-        Ok(if self.turn_order.is_empty() {
-            self.new_turn(g);
-            true
-        } else {
-            false
-        })
+        self.start_character_turn(g);
+        new_round
+    }
+
+    /// Applies the damage over time affecting the character, and removes their expired buffs
+    fn tick_buffs(&mut self, g: &GameDefinition, id: Id<Character>) {
+        // summons removed during their own turn
+        let character = match self.characters.get(id) {
+            Some(character) => character,
+            None => return,
+        };
+        let damage = character
+            .buffs
+            .iter()
+            .filter_map(|instance| match &instance.buff.kind {
+                BuffKind::DoT(damage) => {
+                    let caster = self
+                        .characters
+                        .get(instance.caster)
+                        .expect("Invalid caster id");
+                    // damage over time is never rolled
+                    Some(damage.compute_damage(
+                        &g.classes,
+                        caster,
+                        character,
+                        &CombatModifiers::default(),
+                        &DamageRoll::default(),
+                    ))
+                }
+                _ => None,
+            })
+            .sum::<DamageBreakdown>();
+
+        // the casters share the damage, unless there is only one of them
+        let mut casters = character
+            .buffs
+            .iter()
+            .filter_map(|instance| match instance.buff.kind {
+                BuffKind::DoT(_) => Some(instance.caster),
+                _ => None,
+            });
+        let source = casters
+            .next()
+            .filter(|first| casters.all(|caster| caster == *first));
+
+        if damage != DamageBreakdown::default() {
+            self.damage(g, id, source, damage);
+        }
+        self.characters
+            .get_mut(id)
+            .expect("Invalid character id")
+            .tick_buffs();
+    }
+
+    /// Shields absorb the damage before it reaches the character's health. Negative damage (e.g.
+    /// from a negative modifier) heals the character instead.
+    fn damage(
+        &mut self,
+        g: &GameDefinition,
+        id: Id<Character>,
+        source: Option<Id<Character>>,
+        damage: DamageBreakdown,
+    ) {
+        if damage.total() < 0 {
+            self.heal(g, id, -damage.total());
+            return;
+        }
+
+        let character = self.characters.get_mut(id).expect("Invalid character id");
+        let absorbed = character.absorb(damage.total());
+        if absorbed > 0 {
+            self.events.push(Event::Shielded {
+                character: id,
+                absorbed,
+            });
+        }
+        self.lose_health(id, source, damage, damage.total() - absorbed);
+    }
+
+    fn lose_health(
+        &mut self,
+        id: Id<Character>,
+        source: Option<Id<Character>>,
+        damage: DamageBreakdown,
+        health: i32,
+    ) {
+        let character = self.characters.get_mut(id).expect("Invalid character id");
+        let was_alive = character.is_alive();
+        character.current_health -= health;
+        let died = was_alive && !character.is_alive();
+
+        self.events.push(Event::Damaged {
+            character: id,
+            source,
+            damage,
+        });
+        if died {
+            self.events.push(Event::Died(id));
+        }
+    }
+
+    /// Dead characters cannot be healed, and negative heals do nothing
+    fn heal(&mut self, g: &GameDefinition, id: Id<Character>, amount: i32) {
+        let character = self.characters.get_mut(id).expect("Invalid character id");
+        if !character.is_alive() {
+            return;
+        }
+        let amount = std::cmp::max(0, amount);
+        let class = g.classes.get(character.class).expect("Invalid class id");
+        let missing = std::cmp::max(
+            0,
+            character.effective_health(class) - character.current_health,
+        );
+        let healed = std::cmp::min(amount, missing);
+        character.current_health += healed;
+
+        self.events.push(Event::Healed {
+            character: id,
+            amount: healed,
+            overheal: amount - healed,
+        });
+    }
+
+    /// Removes the summons which died, whose summoner died or whose lifespan ended, along with the
+    /// buffs they cast. The character playing stays in the turn order, for their turn to be ended
+    /// normally.
+    fn remove_summons(&mut self) {
+        loop {
+            let characters = &self.characters;
+            let expired = characters
+                .iter()
+                .filter(|(_, character)| {
+                    let summoner_alive =
+                        |summoner| characters.get(summoner).is_some_and(Character::is_alive);
+                    character.summoner.is_some_and(|summoner| {
+                        !character.is_alive()
+                            || !summoner_alive(summoner)
+                            || character.lifespan == Some(0)
+                    })
+                })
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            // summons of the removed summons are removed on the next iteration
+            if expired.is_empty() {
+                break;
+            }
+
+            for id in &expired {
+                self.characters.remove(*id);
+                self.events.push(Event::Expired(*id));
+            }
+            // the buffs depend on their caster's stats, which are gone with them
+            for (_, character) in self.characters.iter_mut() {
+                character
+                    .buffs
+                    .retain(|instance| !expired.contains(&instance.caster));
+            }
+        }
+
+        let playing = self.turn_order.last().copied();
+        let characters = &self.characters;
+        self.turn_order
+            .retain(|id| Some(*id) == playing || characters.contains(*id));
+        self.next_turn_order.retain(|id| characters.contains(*id));
     }
 
-    fn player_at(&self, cell_id: Id<Cell>) -> Option<(&Id<Character>, &Character)> {
+    /// Living character standing on the cell, if any
+    fn player_at(&self, cell_id: Id<Cell>) -> Option<(Id<Character>, &Character)> {
         self.characters
             .iter()
-            .find(|(_, character)| character.position == cell_id)
+            .find(|(_, character)| character.is_alive() && character.position == cell_id)
+    }
+
+    /// Moves the character to the cell: falling into a hole kills them, and falling from
+    /// high enough deals damage, which the source (who moved them) is responsible for
+    fn land(
+        &mut self,
+        g: &GameDefinition,
+        id: Id<Character>,
+        source: Id<Character>,
+        to: Id<Cell>,
+        fall_height: i32,
+    ) {
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let character = self.characters.get_mut(id).expect("Invalid character id");
+        let from = character.position;
+        if from == to {
+            return;
+        }
+
+        character.position = to;
+        self.events.push(Event::Moved {
+            character: id,
+            from,
+            to,
+        });
+
+        if map[to].is_hole() {
+            self.events.push(Event::Fell {
+                character: id,
+                cell: to,
+            });
+            // no shield saves anyone from falling
+            let health = self
+                .characters
+                .get(id)
+                .expect("Invalid character id")
+                .current_health;
+            self.lose_health(id, Some(source), DamageBreakdown::pure(health), health);
+        } else {
+            let damage = g.rules.fall_damage(fall_height);
+            if damage > 0 {
+                self.damage(g, id, Some(source), DamageBreakdown::pure(damage));
+            }
+        }
+    }
+
+    /// Moves the character by up to `distance` cells in the (dx, dy) direction. The movement
+    /// stops before walls, higher cells and other characters, or when falling into a hole.
+    fn push(
+        &mut self,
+        g: &GameDefinition,
+        id: Id<Character>,
+        source: Id<Character>,
+        (dx, dy): (i32, i32),
+        distance: u32,
+    ) {
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let mut position = self
+            .characters
+            .get(id)
+            .expect("Invalid character id")
+            .position;
+        let mut fall_height = 0;
+
+        for _ in 0..distance {
+            let next = match map.neighbor(position, dx, dy) {
+                Some(next) if next != position => next,
+                _ => break,
+            };
+            let (curr_cell, next_cell) = (&map[position], &map[next]);
+            if next_cell.blocks_movement()
+                || next_cell.height() > curr_cell.height()
+                || self.player_at(next).is_some()
+            {
+                break;
+            }
+
+            // only the highest drop hurts, not the total
+            fall_height = std::cmp::max(fall_height, curr_cell.height() - next_cell.height());
+            position = next;
+            if next_cell.is_hole() {
+                break;
+            }
+        }
+
+        self.land(g, id, source, position, fall_height);
+    }
+
+    /// What the character playing would do by using the skill on the cell
+    pub fn preview_skill(
+        &self,
+        g: &GameDefinition,
+        skill_id: Id<Skill>,
+        cell_id: Id<Cell>,
+    ) -> Result<SkillPreview, Error> {
+        let curr_char = self
+            .characters
+            .get(self.player_to_play())
+            .expect("Invalid character id");
+        let skill = g.skills.get(skill_id).ok_or(Error::InvalidSkill)?;
+        let target_id = self.check_skill(g, curr_char, skill_id, cell_id)?;
+
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let modifiers = g
+            .rules
+            .terrain
+            .modifiers(map, curr_char.position, cell_id, skill);
+        let damage = target_id.map_or(DamagePreview::default(), |id| {
+            let target = self.characters.get(id).expect("Invalid character id");
+            skill
+                .effects
+                .iter()
+                .filter_map(|effect| match &g.effects.get(*effect)?.kind {
+                    EffectKind::DirectDamage(direct_damage) => Some(
+                        direct_damage
+                            .damage
+                            .preview(&g.rules, &g.classes, curr_char, target, &modifiers),
+                    ),
+                    _ => None,
+                })
+                .sum()
+        });
+
+        Ok(SkillPreview {
+            target: target_id,
+            modifiers,
+            hit_chance: GameState::hit_chance(skill, &modifiers),
+            damage,
+        })
+    }
+
+    /// None if the skill always hits
+    fn hit_chance(skill: &Skill, modifiers: &CombatModifiers) -> Option<f32> {
+        skill
+            .precision
+            .map(|precision| (precision + modifiers.precision).clamp(0.0, 1.0))
     }
 
     fn execute_skill(
         &mut self,
+        curr_id: Id<Character>,
         curr_char: Character,
         g: &GameDefinition,
         skill_id: Id<Skill>,
         cell_id: Id<Cell>,
     ) -> Result<(), Error> {
+        let target_id = self.check_skill(g, &curr_char, skill_id, cell_id)?;
+        let attacked = target_id.filter(|id| {
+            self.characters
+                .get(*id)
+                .is_some_and(|target| target.team != curr_char.team)
+        });
+
+        if let Some(id) = attacked {
+            self.react(g, id, ReactionTrigger::Targeted, curr_id);
+        }
+        self.resolve_skill(g, curr_id, skill_id, target_id, cell_id);
+        if let Some(id) = attacked {
+            self.react(g, id, ReactionTrigger::Attacked, curr_id);
+        }
+
+        Ok(())
+    }
+
+    /// Applies a skill which was already checked
+    fn resolve_skill(
+        &mut self,
+        g: &GameDefinition,
+        curr_id: Id<Character>,
+        skill_id: Id<Skill>,
+        target_id: Option<Id<Character>>,
+        cell_id: Id<Cell>,
+    ) {
+        let skill = g.skills.get(skill_id).expect("Invalid skill id");
+        let curr_char = self.characters.get(curr_id).expect("Invalid character id");
+        // the caster can be killed by a reaction to their skill
+        if curr_char.is_alive() {
+            let map = g.maps.get(self.map).expect("Invalid game map id");
+            let modifiers = g
+                .rules
+                .terrain
+                .modifiers(map, curr_char.position, cell_id, skill);
+            let hit = match (target_id, GameState::hit_chance(skill, &modifiers)) {
+                (Some(_), Some(hit_chance)) => self.rng.gen::<f32>() < hit_chance,
+                _ => true,
+            };
+
+            if hit {
+                for effect in &skill.effects {
+                    let effect = g.effects.get(*effect).expect("Invalid effect id");
+                    self.apply_effect(g, curr_id, Some(skill), target_id, cell_id, &effect.kind);
+                }
+            } else if let Some(id) = target_id {
+                self.events.push(Event::Missed(id));
+            }
+        }
+
+        self.characters
+            .get_mut(curr_id)
+            .expect("Invalid character id")
+            .use_skill(skill_id, skill);
+    }
+
+    /// The reactor uses their reactions to the trigger caused by the other character, as long as
+    /// they can. Reactions never trigger other reactions.
+    fn react(
+        &mut self,
+        g: &GameDefinition,
+        reactor_id: Id<Character>,
+        trigger: ReactionTrigger,
+        other_id: Id<Character>,
+    ) {
+        let class = {
+            let reactor = self
+                .characters
+                .get(reactor_id)
+                .expect("Invalid character id");
+            g.classes.get(reactor.class).expect("Invalid class id")
+        };
+
+        for reaction in class
+            .reactions
+            .iter()
+            .filter(|reaction| reaction.trigger == trigger)
+        {
+            // the previous reactions may have changed the state
+            let reactor = self
+                .characters
+                .get(reactor_id)
+                .expect("Invalid character id")
+                .clone();
+            if !reactor.is_alive() || reactor.is_stunned() || reactor.reactions_left == 0 {
+                return;
+            }
+
+            let target = match reaction.target {
+                PassiveTarget::Owner => reactor_id,
+                PassiveTarget::Other => other_id,
+            };
+            let cell_id = self
+                .characters
+                .get(target)
+                .expect("Invalid character id")
+                .position;
+            let target_id = match self.check_skill(g, &reactor, reaction.skill, cell_id) {
+                Ok(target_id) => target_id,
+                Err(_) => continue,
+            };
+
+            self.characters
+                .get_mut(reactor_id)
+                .expect("Invalid character id")
+                .reactions_left -= 1;
+            self.events.push(Event::Reacted {
+                character: reactor_id,
+                skill: reaction.skill,
+            });
+            self.resolve_skill(g, reactor_id, reaction.skill, target_id, cell_id);
+        }
+    }
+
+    /// Checks whether the character can use the skill on the cell, and returns the targeted
+    /// character if there is one
+    fn check_skill(
+        &self,
+        g: &GameDefinition,
+        curr_char: &Character,
+        skill_id: Id<Skill>,
+        cell_id: Id<Cell>,
+    ) -> Result<Option<Id<Character>>, Error> {
         let skill = g.skills.get(skill_id).ok_or(Error::InvalidSkill)?;
-        let target = self.player_at(cell_id);
+        curr_char.can_use_skill(skill_id, skill)?;
+        if skill.magical && curr_char.is_silenced() {
+            return Err(Error::Silenced);
+        }
 
-        if !GameState::check_target(&curr_char, &target, skill.range.target) {
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        if !map.is_valid_cell(cell_id) {
+            return Err(Error::InvalidTarget);
+        }
+
+        let target = self
+            .player_at(cell_id)
+            .map(|(id, character)| (id, character.clone()));
+
+        if !GameState::check_target(curr_char, target.as_ref(), skill.range.target) {
             return Err(Error::InvalidTarget);
         }
 
-        if !self.check_range(g, curr_char.position, cell_id, skill.range) {
+        if let Some(taunter) = curr_char.taunted_by() {
+            let taunter_alive = self
+                .characters
+                .get(taunter)
+                .is_some_and(Character::is_alive);
+            let allowed = match &target {
+                Some((id, target)) => *id == taunter || target.team == curr_char.team,
+                None => false,
+            };
+            if taunter_alive && !allowed {
+                return Err(Error::Taunted);
+            }
+        }
+
+        let mut range = skill.range;
+        range.max += g
+            .rules
+            .terrain
+            .range_bonus(map, curr_char.position, cell_id, skill);
+        if !self.check_range(g, curr_char.position, cell_id, range) {
             return Err(Error::InvalidRange);
         }
 
-        // TODO check LOS
+        if range.needs_los && !map.has_line_of_sight(curr_char.position, cell_id) {
+            return Err(Error::NoLineOfSight);
+        }
 
-        // FIXME: this is a borrow-checker workaround... but probably the only actual one..?
-        let mut game_state_updates = HashSet::<(Id<Character>, i32)>::new();
-        // TODO: compute if hit?
-        if true {
-            if let Some((id, target)) = target {
-                for effect in &skill.effects {
-                    let effect = g.effects.get(*effect).expect("Invalid effect id");
-                    match &effect.kind {
-                        // TODO
-                        EffectKind::Buff(_) => unimplemented!(),
-
-                        // TODO: add somewhere if skills can attack other cells than just the
-                        // target
-                        EffectKind::DirectDamage(direct_damage) => {
-                            let damage = direct_damage
-                                .damage
-                                .compute_damage(&g.classes, &curr_char, target);
-                            game_state_updates.insert((*id, damage));
-                        }
-                    }
+        let needs_free_cell = skill.effects.iter().any(|effect| {
+            let effect = g.effects.get(*effect).expect("Invalid effect id");
+            matches!(effect.kind, EffectKind::Teleport | EffectKind::Summon(_))
+        });
+        let cell = &map[cell_id];
+        if needs_free_cell && (target.is_some() || !cell.can_stand_on()) {
+            return Err(Error::InvalidTarget);
+        }
+
+        Ok(target.map(|(id, _)| id))
+    }
+
+    /// Effects are applied one after the other, each one seeing the state left by the previous ones
+    fn apply_effect(
+        &mut self,
+        g: &GameDefinition,
+        caster_id: Id<Character>,
+        skill: Option<&Skill>,
+        target_id: Option<Id<Character>>,
+        cell_id: Id<Cell>,
+        effect: &EffectKind,
+    ) {
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let caster = self
+            .characters
+            .get(caster_id)
+            .expect("Invalid character id")
+            .clone();
+        // effects after the one killing the target have nothing to apply to
+        let target = target_id.and_then(|id| {
+            self.characters
+                .get(id)
+                .filter(|target| target.is_alive())
+                .map(|target| (id, target.clone()))
+        });
+
+        match (effect, target) {
+            // TODO: use the buff's range
+            (EffectKind::Buff(buff), Some((id, _))) => {
+                let success = match buff.success_rate {
+                    Some(rate) => self.rng.gen::<f32>() < rate,
+                    None => true,
+                };
+                if success {
+                    let instance = BuffInstance::new(buff.clone(), caster_id);
+                    let target = self.characters.get_mut(id).expect("Invalid character id");
+                    target.buffs.push(instance);
                 }
             }
-        }
 
-        // TODO stuff if dead
-        for (id, damage) in game_state_updates {
-            let character = self.characters.get_mut(id).expect("Invalid character id");
-            character.current_health -= damage;
+            // TODO: add somewhere if skills can attack other cells than just the
+            // target
+            (EffectKind::DirectDamage(direct_damage), Some((id, target))) => {
+                // passives are not affected by the terrain
+                let modifiers = skill.map_or(CombatModifiers::default(), |skill| {
+                    g.rules
+                        .terrain
+                        .modifiers(map, caster.position, target.position, skill)
+                });
+                let roll = direct_damage
+                    .damage
+                    .roll(&g.rules, &g.classes, &caster, &mut self.rng);
+                let damage = direct_damage
+                    .damage
+                    .compute_damage(&g.classes, &caster, &target, &modifiers, &roll);
+                self.damage(g, id, Some(caster_id), damage);
+            }
+
+            (EffectKind::Heal(heal), Some((id, target))) => {
+                let c_caster = g.classes.get(caster.class).expect("Invalid class id");
+                let c_target = g.classes.get(target.class).expect("Invalid class id");
+                let amount = heal.amount(&caster, c_caster, &target, c_target);
+                self.heal(g, id, amount);
+            }
+
+            (EffectKind::Knockback(distance), Some((id, target))) => {
+                let direction = map.direction(caster.position, target.position);
+                self.push(g, id, caster_id, direction, *distance);
+            }
+            (EffectKind::Pull(distance), Some((id, target))) => {
+                let direction = map.direction(target.position, caster.position);
+                self.push(g, id, caster_id, direction, *distance);
+            }
+            (EffectKind::Swap, Some((id, target))) => {
+                let (from, to) = (caster.position, target.position);
+                self.land(
+                    g,
+                    caster_id,
+                    caster_id,
+                    to,
+                    map[from].height() - map[to].height(),
+                );
+                self.land(
+                    g,
+                    id,
+                    caster_id,
+                    from,
+                    map[to].height() - map[from].height(),
+                );
+            }
+            (EffectKind::Teleport, None) => {
+                let height = map[caster.position].height() - map[cell_id].height();
+                self.land(g, caster_id, caster_id, cell_id, height);
+            }
+
+            (EffectKind::Summon(summon), None) => {
+                let class = g.classes.get(summon.class).expect("Invalid class id");
+                let mut character =
+                    Character::new(summon.class, cell_id, class, &*summon.name, caster.team);
+                character.summoner = Some(caster_id);
+                character.lifespan = summon.lifespan;
+                let id = self.characters.insert(character);
+
+                // both orders are already rolled: the summon plays last in each of them
+                self.turn_order.insert(0, id);
+                self.next_turn_order.insert(0, id);
+                self.events.push(Event::Summoned {
+                    character: id,
+                    summoner: caster_id,
+                });
+            }
+
+            (EffectKind::Script(script), Some((id, target))) => {
+                let context = ScriptContext {
+                    map,
+                    caster: &caster,
+                    c_caster: g.classes.get(caster.class).expect("Invalid class id"),
+                    target: &target,
+                    c_target: g.classes.get(target.class).expect("Invalid class id"),
+                };
+                for action in script.run(&context) {
+                    self.apply_script_action(g, caster_id, id, action);
+                }
+            }
+
+            _ => (),
         }
+    }
 
-        Ok(())
+    fn apply_script_action(
+        &mut self,
+        g: &GameDefinition,
+        caster_id: Id<Character>,
+        target_id: Id<Character>,
+        action: ScriptAction,
+    ) {
+        // the target may have died or moved because of the previous actions
+        let (caster, target) = match (
+            self.characters.get(caster_id),
+            self.characters.get(target_id),
+        ) {
+            (Some(caster), Some(target)) if target.is_alive() => (caster.clone(), target.clone()),
+            _ => return,
+        };
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+
+        match action {
+            ScriptAction::Damage(damage_type, amount) => {
+                let damage = Damage::flat(damage_type, amount).compute_damage(
+                    &g.classes,
+                    &caster,
+                    &target,
+                    &CombatModifiers::default(),
+                    &DamageRoll::default(),
+                );
+                self.damage(g, target_id, Some(caster_id), damage);
+            }
+            ScriptAction::Heal(amount) => self.heal(g, target_id, amount),
+            ScriptAction::Buff {
+                name,
+                kind,
+                duration,
+            } => {
+                let buff = Buff {
+                    name,
+                    range: None,
+                    success_rate: None,
+                    duration,
+                    kind,
+                };
+                let target = self
+                    .characters
+                    .get_mut(target_id)
+                    .expect("Invalid character id");
+                target.buffs.push(BuffInstance::new(buff, caster_id));
+            }
+            ScriptAction::Push(distance) => {
+                let direction = map.direction(caster.position, target.position);
+                self.push(g, target_id, caster_id, direction, distance);
+            }
+            ScriptAction::Pull(distance) => {
+                let direction = map.direction(target.position, caster.position);
+                self.push(g, target_id, caster_id, direction, distance);
+            }
+        }
     }
 
     fn check_target(
         attacker: &Character,
-        target_opt: &Option<(&Id<Character>, &Character)>,
+        target_opt: Option<&(Id<Character>, Character)>,
         target_kind: Target,
     ) -> bool {
         if let Some((_, target)) = target_opt {
@@ -210,28 +1004,907 @@ impl GameState {
         }
     }
 
+    /// Queues the passives of the owner reacting to the trigger, if they are still alive
+    fn trigger<'g>(
+        &self,
+        g: &'g GameDefinition,
+        owner: Id<Character>,
+        trigger: Trigger,
+        other: Option<Id<Character>>,
+        triggered: &mut Vec<Triggered<'g>>,
+    ) {
+        let character = self.characters.get(owner).expect("Invalid character id");
+        if !character.is_alive() {
+            return;
+        }
+        let class = g.classes.get(character.class).expect("Invalid class id");
+        triggered.extend(
+            class
+                .passives
+                .iter()
+                .filter(|passive| passive.trigger == trigger)
+                .map(|passive| (owner, passive, other)),
+        );
+    }
+
+    /// Passives triggered by the events from `start` onward
+    fn triggered_passives<'g>(&self, g: &'g GameDefinition, start: usize) -> Vec<Triggered<'g>> {
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let mut triggered = Vec::new();
+        let mut last_source = None;
+
+        for event in &self.events[start..] {
+            match *event {
+                Event::Damaged {
+                    character, source, ..
+                } => {
+                    last_source = source.filter(|source| *source != character);
+                    if let Some(source) = last_source {
+                        self.trigger(g, character, Trigger::Hit, Some(source), &mut triggered);
+                    }
+                }
+                // deaths always directly follow the damage which caused them
+                Event::Died(victim) => {
+                    if let Some(killer) = last_source {
+                        self.trigger(g, killer, Trigger::Kill, Some(victim), &mut triggered);
+                    }
+                    let team = self
+                        .characters
+                        .get(victim)
+                        .expect("Invalid character id")
+                        .team;
+                    for (id, character) in self.characters.iter() {
+                        if id != victim && character.team == team {
+                            self.trigger(g, id, Trigger::AllyDeath, Some(victim), &mut triggered);
+                        }
+                    }
+                }
+                Event::Moved {
+                    character: mover,
+                    from,
+                    to,
+                } => {
+                    let team = self
+                        .characters
+                        .get(mover)
+                        .expect("Invalid character id")
+                        .team;
+                    for (id, character) in self.characters.iter() {
+                        let newly_adjacent = character.is_alive()
+                            && character.team != team
+                            && map.distance(character.position, to) == 1
+                            && map.distance(character.position, from) != 1;
+                        if newly_adjacent {
+                            self.trigger(
+                                g,
+                                mover,
+                                Trigger::AdjacentToEnemy,
+                                Some(id),
+                                &mut triggered,
+                            );
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        triggered
+    }
+
+    /// Applies the passives, then the ones triggered by their effects and so on, up to the max
+    /// depth allowed by the rules
+    fn apply_passives<'g>(&mut self, g: &'g GameDefinition, mut triggered: Vec<Triggered<'g>>) {
+        for _ in 0..g.rules.max_passive_depth {
+            if triggered.is_empty() {
+                return;
+            }
+
+            let start = self.events.len();
+            for (owner, passive, other) in triggered {
+                let target = match passive.target {
+                    PassiveTarget::Owner => Some(owner),
+                    PassiveTarget::Other => other,
+                };
+                let owner_alive = self.characters.get(owner).is_some_and(Character::is_alive);
+                let target = match target {
+                    Some(target) if owner_alive => target,
+                    _ => continue,
+                };
+
+                let cell = self
+                    .characters
+                    .get(target)
+                    .expect("Invalid character id")
+                    .position;
+                for effect in &passive.effects {
+                    let effect = g.effects.get(*effect).expect("Invalid effect id");
+                    self.apply_effect(g, owner, None, Some(target), cell, &effect.kind);
+                }
+            }
+            triggered = self.triggered_passives(g, start);
+        }
+    }
+
+    /// Returns the cost of the move
     fn execute_move(
         &mut self,
-        curr_char: Character,
+        curr_id: Id<Character>,
+        curr_char: &Character,
         g: &GameDefinition,
         cell_id: Id<Cell>,
-    ) -> Result<(), Error> {
+        movement_points: i32,
+    ) -> Result<i32, Error> {
         assert!(!self.turn_order.is_empty());
 
-        let class = g.classes.get(curr_char.class).expect("Invalid class id");
-        if g.maps
-            .get(self.map)
-            .expect("Invalid game map id")
-            .can_move_to(
-                curr_char.position,
-                cell_id,
-                curr_char.effective_swiftness(class),
-            )
+        if self.player_at(cell_id).is_some() {
+            return Err(Error::CellOccupied);
+        }
+
+        let map = g.maps.get(self.map).expect("Invalid game map id");
+        let from = curr_char.position;
+        let cost = map
+            .path_cost(from, cell_id, movement_points)
+            .ok_or(Error::MoveCellTooFar)?;
+
+        let left_enemies = self
+            .characters
+            .iter()
+            .filter(|(_, character)| {
+                character.is_alive()
+                    && character.team != curr_char.team
+                    && map.distance(character.position, from) == 1
+                    && map.distance(character.position, cell_id) != 1
+            })
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in left_enemies {
+            self.react(g, id, ReactionTrigger::EnemyLeaves, curr_id);
+        }
+
+        // the opportunity attacks may have stopped the character, who still spent the points
+        let character = self.characters.get(curr_id).expect("Invalid character id");
+        if !character.is_alive()
+            || character.is_stunned()
+            || character.is_rooted()
+            || character.position != from
+            || self.player_at(cell_id).is_some()
         {
-            self.turn_state = TurnState::ActionOnly;
-            Ok(())
-        } else {
+            return Ok(cost);
+        }
+
+        self.characters
+            .get_mut(curr_id)
+            .expect("Invalid character id")
+            .position = cell_id;
+        self.events.push(Event::Moved {
+            character: curr_id,
+            from,
+            to: cell_id,
+        });
+        Ok(cost)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, GameState, TurnState};
+    use crate::game::character::{BuffInstance, Character};
+    use crate::game::damage::DamageBreakdown;
+    use crate::game::effect::{Buff, BuffKind, Effect, EffectKind, Summon, Target};
+    use crate::game::error::Error;
+    use crate::game::event::Event;
+    use crate::game::game_definition::GameDefinition;
+    use crate::game::id_map::Id;
+    use crate::game::lobby::Lobby;
+    use crate::game::passive::{Passive, PassiveTarget, Trigger};
+    use crate::game::reaction::{Reaction, ReactionTrigger};
+    use crate::game::rules::TurnModel;
+    use crate::game::script::Script;
+    use crate::game::test_utils::{game_definition, running_game, Duel};
+    use serde_json::json;
+
+    /// Passes until the character plays again, the given number of rounds later
+    fn skip_rounds(g: &GameDefinition, gs: &mut GameState, character: Id<Character>, rounds: u32) {
+        let mut rounds_left = rounds;
+        while rounds_left > 0 || gs.player_to_play() != character {
+            if gs.next_action(g, Action::Pass).unwrap() {
+                rounds_left = rounds_left
+                    .checked_sub(1)
+                    .expect("Character did not play in the last round");
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_phase() {
+        let g = game_definition();
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+
+        let cell = duel.toward_enemy(1);
+        gs.next_action(&g, Action::Movement(cell)).unwrap();
+        assert_eq!(gs.characters.get(duel.attacker).unwrap().position, cell);
+        assert_eq!(gs.turn_state, TurnState::ActionOnly);
+        assert!(matches!(
+            gs.next_action(&g, Action::Movement(duel.start)),
+            Err(Error::AlreadyMoved)
+        ));
+
+        gs.next_action(&g, Action::Pass).unwrap();
+        assert_eq!(gs.player_to_play(), duel.enemy);
+        assert_eq!(gs.turn_state, TurnState::MoveOrAction);
+    }
+
+    #[test]
+    fn test_action_points() {
+        let mut g = game_definition();
+        g.rules.turn_model = TurnModel::ActionPoints;
+        let class = g.classes.get_mut(Id::new(0)).unwrap();
+        class.swiftness = 4;
+        class.action_points = 3;
+        g.skills.get_mut(Id::new(0)).unwrap().action_points = 2;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+
+        // move, attack, move back
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        assert!(matches!(
+            gs.next_action(&g, Action::Movement(duel.enemy_cell)),
+            Err(Error::CellOccupied)
+        ));
+        gs.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+        let cell = duel.toward_enemy(2);
+        gs.next_action(&g, Action::Movement(cell)).unwrap();
+        assert_eq!(
+            gs.turn_state,
+            TurnState::Points {
+                action: 1,
+                movement: 0
+            }
+        );
+        assert_eq!(gs.characters.get(duel.attacker).unwrap().position, cell);
+
+        assert!(matches!(
+            gs.next_action(&g, Action::Movement(duel.start)),
             Err(Error::MoveCellTooFar)
+        ));
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell)),
+            Err(Error::NotEnoughActionPoints)
+        ));
+
+        gs.next_action(&g, Action::Pass).unwrap();
+        assert_eq!(gs.player_to_play(), duel.enemy);
+        assert_eq!(
+            gs.turn_state,
+            TurnState::Points {
+                action: 3,
+                movement: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_skill_cooldown() {
+        let mut g = game_definition();
+        g.rules.turn_model = TurnModel::ActionPoints;
+        g.classes.get_mut(Id::new(0)).unwrap().action_points = 2;
+        g.skills.get_mut(Id::new(0)).unwrap().cooldown = 2;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let slash = Action::Skill(Id::new(0), duel.enemy_cell);
+
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        gs.next_action(&g, slash.clone()).unwrap();
+        assert!(matches!(
+            gs.next_action(&g, slash.clone()),
+            Err(Error::SkillOnCooldown)
+        ));
+
+        skip_rounds(&g, &mut gs, duel.attacker, 1);
+        assert!(matches!(
+            gs.next_action(&g, slash.clone()),
+            Err(Error::SkillOnCooldown)
+        ));
+        skip_rounds(&g, &mut gs, duel.attacker, 1);
+        gs.next_action(&g, slash).unwrap();
+    }
+
+    #[test]
+    fn test_skill_charges() {
+        let mut g = game_definition();
+        g.rules.turn_model = TurnModel::ActionPoints;
+        g.classes.get_mut(Id::new(0)).unwrap().action_points = 3;
+        let skill = g.skills.get_mut(Id::new(0)).unwrap();
+        skill.charges = Some(2);
+        skill.recharge = 2;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let slash = Action::Skill(Id::new(0), duel.enemy_cell);
+
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        gs.next_action(&g, slash.clone()).unwrap();
+        gs.next_action(&g, slash.clone()).unwrap();
+        assert!(matches!(
+            gs.next_action(&g, slash.clone()),
+            Err(Error::NoChargesLeft)
+        ));
+
+        // the charges come back one at a time
+        skip_rounds(&g, &mut gs, duel.attacker, 1);
+        assert!(matches!(
+            gs.next_action(&g, slash.clone()),
+            Err(Error::NoChargesLeft)
+        ));
+        skip_rounds(&g, &mut gs, duel.attacker, 1);
+        gs.next_action(&g, slash.clone()).unwrap();
+        assert!(matches!(
+            gs.next_action(&g, slash),
+            Err(Error::NoChargesLeft)
+        ));
+    }
+
+    #[test]
+    fn test_crowd_control() {
+        let mut g = game_definition();
+        let stun = Buff {
+            name: "Stun".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 1,
+            kind: BuffKind::Stun,
+        };
+        g.effects.get_mut(Id::new(0)).unwrap().kind = EffectKind::Buff(stun);
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+
+        // the enemy plays right after the attacker, and has its turn skipped
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        assert!(gs
+            .next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap());
+        assert!(gs.characters.get(duel.enemy).unwrap().buffs.is_empty());
+
+        let buff = |kind| {
+            let buff = Buff {
+                name: "".to_owned(),
+                range: None,
+                success_rate: None,
+                duration: 1,
+                kind,
+            };
+            BuffInstance::new(buff, duel.enemy)
+        };
+        let player = gs.player_to_play();
+        let character = gs.characters.get_mut(player).unwrap();
+        character.buffs = vec![buff(BuffKind::Root), buff(BuffKind::Stun)];
+
+        let position = character.position;
+        assert!(matches!(
+            gs.next_action(&g, Action::Movement(position)),
+            Err(Error::Stunned)
+        ));
+        gs.characters.get_mut(player).unwrap().buffs.pop();
+        assert!(matches!(
+            gs.next_action(&g, Action::Movement(position)),
+            Err(Error::Rooted)
+        ));
+
+        g.skills.get_mut(Id::new(0)).unwrap().magical = true;
+        let character = gs.characters.get_mut(player).unwrap();
+        character.buffs = vec![buff(BuffKind::Silence)];
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), position)),
+            Err(Error::Silenced)
+        ));
+    }
+
+    #[test]
+    fn test_taunt() {
+        let mut g = game_definition();
+        g.skills.get_mut(Id::new(0)).unwrap().range.max = 8;
+
+        // two characters on each side, all in range of each other
+        let mut lobby = Lobby::new(&g, Id::new(0), 2).unwrap();
+        for (team, cell) in &[(0, 0), (0, 20), (1, 4), (1, 24)] {
+            let (id, token) = lobby
+                .join(None, "c", Id::new(0), Id::new(*team), Id::new(*cell))
+                .unwrap();
+            lobby.set_ready(&token, id, true).unwrap();
+        }
+        let mut gs = lobby.try_start().unwrap().0;
+
+        let player = gs.player_to_play();
+        let team = gs.characters.get(player).unwrap().team;
+        let enemies = gs
+            .characters
+            .iter()
+            .filter(|(_, character)| character.team != team)
+            .map(|(id, character)| (id, character.position))
+            .collect::<Vec<_>>();
+        let (taunter, taunter_cell) = enemies[0];
+        let (_, other_cell) = enemies[1];
+
+        let taunt = Buff {
+            name: "Taunt".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 1,
+            kind: BuffKind::Taunt,
+        };
+        let character = gs.characters.get_mut(player).unwrap();
+        character.buffs = vec![BuffInstance::new(taunt, taunter)];
+
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), other_cell)),
+            Err(Error::Taunted)
+        ));
+        gs.next_action(&g, Action::Skill(Id::new(0), taunter_cell))
+            .unwrap();
+        assert_eq!(
+            gs.characters.get(taunter).unwrap().current_health,
+            g.classes.get(Id::new(0)).unwrap().health - 5
+        );
+    }
+
+    #[test]
+    fn test_teleport() {
+        let mut g = game_definition();
+        g.effects.get_mut(Id::new(0)).unwrap().kind = EffectKind::Teleport;
+        let skill = g.skills.get_mut(Id::new(0)).unwrap();
+        skill.range.target = Target::Anything;
+        skill.range.max = 2;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let hole = duel.toward_enemy(1);
+        g.maps.get_mut(Id::new(0)).unwrap().data[hole.raw()] =
+            serde_json::from_value(json!({ "height": 0, "attribute": "Hole" })).unwrap();
+
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), hole)),
+            Err(Error::InvalidTarget)
+        ));
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), Id::new(999))),
+            Err(Error::InvalidTarget)
+        ));
+        let cell = duel.toward_enemy(2);
+        gs.next_action(&g, Action::Skill(Id::new(0), cell)).unwrap();
+        assert_eq!(gs.characters.get(duel.attacker).unwrap().position, cell);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let mut g = game_definition();
+        g.skills.get_mut(Id::new(0)).unwrap().range.max = 4;
+
+        let gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        g.maps.get_mut(Id::new(0)).unwrap().data[duel.toward_enemy(2).raw()] =
+            serde_json::from_value(json!({ "height": 0, "attribute": "Wall" })).unwrap();
+
+        let mut blocked = gs.clone();
+        assert!(matches!(
+            blocked.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell)),
+            Err(Error::NoLineOfSight)
+        ));
+
+        // e.g. thrown over the wall
+        g.skills.get_mut(Id::new(0)).unwrap().range.needs_los = false;
+        let mut thrown = gs;
+        thrown
+            .next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_pull_into_hole() {
+        let mut g = game_definition();
+        g.effects.get_mut(Id::new(0)).unwrap().kind = EffectKind::Pull(3);
+        g.skills.get_mut(Id::new(0)).unwrap().range.max = 2;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let hole = duel.toward_enemy(3);
+        g.maps.get_mut(Id::new(0)).unwrap().data[hole.raw()] =
+            serde_json::from_value(json!({ "height": 0, "attribute": "Hole" })).unwrap();
+
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(2)))
+            .unwrap();
+        assert!(gs
+            .next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap());
+
+        assert_eq!(
+            gs.events(),
+            &[
+                Event::Moved {
+                    character: duel.enemy,
+                    from: duel.enemy_cell,
+                    to: hole,
+                },
+                Event::Fell {
+                    character: duel.enemy,
+                    cell: hole,
+                },
+                Event::Damaged {
+                    character: duel.enemy,
+                    source: Some(duel.attacker),
+                    damage: DamageBreakdown::pure(20),
+                },
+                Event::Died(duel.enemy),
+            ]
+        );
+        assert_eq!(gs.turn_order, vec![duel.attacker]);
+    }
+
+    #[test]
+    fn test_preview_high_ground() {
+        let mut g = game_definition();
+        g.rules.terrain.damage_per_height = 0.5;
+        g.classes.get_mut(Id::new(0)).unwrap().swiftness = 4;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (cell, enemy) = (duel.toward_enemy(3), duel.enemy_cell);
+        g.maps.get_mut(Id::new(0)).unwrap().data[cell.raw()] =
+            serde_json::from_value(json!({ "height": 1, "attribute": "None" })).unwrap();
+
+        gs.next_action(&g, Action::Movement(cell)).unwrap();
+        let preview = gs.preview_skill(&g, Id::new(0), enemy).unwrap();
+        assert_eq!(preview.modifiers.height_difference, 1);
+        assert_eq!(preview.hit_chance, None);
+        // 6 * 1.5 - 1 armor
+        assert_eq!(preview.damage.max.total(), 8);
+
+        gs.next_action(&g, Action::Skill(Id::new(0), enemy))
+            .unwrap();
+        let (_, target) = gs.player_at(enemy).unwrap();
+        assert_eq!(target.current_health, 20 - preview.damage.min.total());
+    }
+
+    #[test]
+    fn test_heal_and_shield() {
+        let mut g = game_definition();
+        g.effects.get_mut(Id::new(0)).unwrap().kind =
+            EffectKind::Heal(serde_json::from_value(json!({ "flat": 10 })).unwrap());
+        let skill = g.skills.get_mut(Id::new(0)).unwrap();
+        skill.range.target = Target::Anyone;
+        skill.range.min = 0;
+
+        let mut gs = running_game(&g);
+        let healer = gs.player_to_play();
+        let position = gs.characters.get(healer).unwrap().position;
+        gs.characters.get_mut(healer).unwrap().current_health = 15;
+
+        gs.next_action(&g, Action::Skill(Id::new(0), position))
+            .unwrap();
+        assert_eq!(gs.characters.get(healer).unwrap().current_health, 20);
+        assert_eq!(
+            gs.events(),
+            &[Event::Healed {
+                character: healer,
+                amount: 5,
+                overheal: 5,
+            }]
+        );
+
+        let shield = Buff {
+            name: "Shield".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 2,
+            kind: BuffKind::Shield(4),
+        };
+        let character = gs.characters.get_mut(healer).unwrap();
+        character.buffs.push(BuffInstance::new(shield, healer));
+        gs.events.clear();
+        gs.damage(&g, healer, None, DamageBreakdown::pure(6));
+
+        let character = gs.characters.get(healer).unwrap();
+        assert_eq!(character.current_health, 18);
+        assert!(character.buffs.is_empty());
+        assert_eq!(
+            gs.events()[0],
+            Event::Shielded {
+                character: healer,
+                absorbed: 4,
+            }
+        );
+
+        // negative damage heals, without going past the max health
+        gs.events.clear();
+        gs.damage(&g, healer, None, DamageBreakdown::pure(-5));
+        assert_eq!(gs.characters.get(healer).unwrap().current_health, 20);
+        assert_eq!(
+            gs.events(),
+            &[Event::Healed {
+                character: healer,
+                amount: 2,
+                overheal: 3,
+            }]
+        );
+
+        // negative heals do nothing, rather than dealing damage which would bypass the shields
+        gs.events.clear();
+        gs.heal(&g, healer, -5);
+        assert_eq!(gs.characters.get(healer).unwrap().current_health, 20);
+        assert_eq!(
+            gs.events(),
+            &[Event::Healed {
+                character: healer,
+                amount: 0,
+                overheal: 0,
+            }]
+        );
+
+        // nor do they revive the dead
+        gs.characters.get_mut(healer).unwrap().current_health = 0;
+        gs.damage(&g, healer, None, DamageBreakdown::pure(-5));
+        assert_eq!(gs.characters.get(healer).unwrap().current_health, 0);
+    }
+
+    #[test]
+    fn test_script() {
+        let mut g = game_definition();
+        // invalid scripts prevent the definition from being loaded
+        let invalid = json!({ "Script": "heal(1; root(2)" });
+        assert!(serde_json::from_value::<EffectKind>(invalid).is_err());
+        let script = Script::new("damage(pure, target.health / 2); root(2)").unwrap();
+        g.effects.get_mut(Id::new(0)).unwrap().kind = EffectKind::Script(script);
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        gs.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+
+        let enemy = gs.characters.get(duel.enemy).unwrap();
+        assert_eq!(enemy.current_health, 10);
+        assert!(enemy.is_rooted());
+    }
+
+    #[test]
+    fn test_passive_chain() {
+        let mut g = game_definition();
+        // both characters strike back when hit, which would go on forever without a depth limit
+        g.classes.get_mut(Id::new(0)).unwrap().passives = vec![Passive {
+            name: "Thorns".to_owned(),
+            trigger: Trigger::Hit,
+            target: PassiveTarget::Other,
+            effects: vec![Id::new(0)],
+        }];
+        g.rules.max_passive_depth = 3;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (attacker, enemy) = (duel.attacker, duel.enemy);
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        gs.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+
+        // the skill, then the enemy's passive, the attacker's, and the enemy's again
+        let sources = gs
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                Event::Damaged { source, .. } => *source,
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sources, vec![attacker, enemy, attacker, enemy]);
+        assert_eq!(gs.characters.get(attacker).unwrap().current_health, 10);
+        assert_eq!(gs.characters.get(enemy).unwrap().current_health, 10);
+    }
+
+    #[test]
+    fn test_reactions() {
+        let mut g = game_definition();
+        let reaction = |trigger| Reaction {
+            trigger,
+            target: PassiveTarget::Other,
+            skill: Id::new(0),
+        };
+        g.classes.get_mut(Id::new(0)).unwrap().reactions = vec![
+            reaction(ReactionTrigger::EnemyLeaves),
+            reaction(ReactionTrigger::Attacked),
+        ];
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (attacker, enemy) = (duel.attacker, duel.enemy);
+        gs.next_action(&g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+
+        // the enemy strikes back, which uses their only reaction of the round
+        gs.next_action(&g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+        assert!(gs.events().contains(&Event::Reacted {
+            character: enemy,
+            skill: Id::new(0),
+        }));
+        assert_eq!(gs.characters.get(attacker).unwrap().current_health, 15);
+        assert_eq!(gs.characters.get(enemy).unwrap().current_health, 15);
+        assert_eq!(gs.characters.get(enemy).unwrap().reactions_left, 0);
+
+        // moving away from the attacker provokes an opportunity attack
+        assert_eq!(gs.player_to_play(), enemy);
+        let away = Id::new(duel.enemy_cell.raw() + 5);
+        gs.next_action(&g, Action::Movement(away)).unwrap();
+        assert_eq!(gs.characters.get(enemy).unwrap().current_health, 10);
+        assert_eq!(gs.characters.get(enemy).unwrap().position, away);
+        assert_eq!(gs.characters.get(attacker).unwrap().reactions_left, 0);
+    }
+
+    #[test]
+    fn test_summons() {
+        let mut g = game_definition();
+        let summon = |lifespan| {
+            EffectKind::Summon(Summon {
+                class: Id::new(0),
+                name: "Totem".to_owned(),
+                lifespan,
+            })
+        };
+        g.effects.get_mut(Id::new(0)).unwrap().kind = summon(Some(1));
+        g.skills.get_mut(Id::new(0)).unwrap().range.target = Target::Anything;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (summoner, cell) = (duel.attacker, duel.toward_enemy(1));
+        gs.next_action(&g, Action::Skill(Id::new(0), cell)).unwrap();
+
+        let totem = gs.player_at(cell).unwrap().0;
+        assert_eq!(
+            gs.events(),
+            &[Event::Summoned {
+                character: totem,
+                summoner,
+            }]
+        );
+        let character = gs.characters.get(totem).unwrap();
+        assert_eq!(character.team, gs.characters.get(summoner).unwrap().team);
+        assert_eq!(gs.next_turn_order.first(), Some(&totem));
+
+        // the totem plays after the enemy, then disappears when the next round starts
+        gs.next_action(&g, Action::Pass).unwrap();
+        assert_eq!(gs.player_to_play(), totem);
+        assert!(gs.next_action(&g, Action::Pass).unwrap());
+        assert!(gs.events().contains(&Event::Expired(totem)));
+        assert!(gs.characters.get(totem).is_none());
+        assert!(!gs.turn_order.contains(&totem) && !gs.next_turn_order.contains(&totem));
+
+        // summons without a lifespan disappear with their summoner
+        g.effects.get_mut(Id::new(0)).unwrap().kind = summon(None);
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (summoner, cell) = (duel.attacker, duel.toward_enemy(1));
+        gs.next_action(&g, Action::Skill(Id::new(0), cell)).unwrap();
+        let totem = gs.player_at(cell).unwrap().0;
+
+        gs.damage(&g, summoner, None, DamageBreakdown::pure(20));
+        gs.remove_summons();
+        assert!(gs.events().contains(&Event::Expired(totem)));
+        assert!(gs.characters.get(totem).is_none());
+    }
+
+    #[test]
+    fn test_expired_summon_buffs() {
+        let mut g = game_definition();
+        let poison = Effect {
+            id: Id::new(1),
+            kind: EffectKind::Buff(Buff {
+                name: "Poison".to_owned(),
+                range: None,
+                success_rate: None,
+                duration: 3,
+                kind: BuffKind::DoT(serde_json::from_value(json!({ "flat_pure": 2 })).unwrap()),
+            }),
+        };
+        let summon = EffectKind::Summon(Summon {
+            class: Id::new(0),
+            name: "Totem".to_owned(),
+            lifespan: Some(2),
+        });
+        g.effects.get_mut(Id::new(0)).unwrap().kind = summon;
+        g.effects = g
+            .effects
+            .into_iter()
+            .chain(std::iter::once((Id::new(1), poison)))
+            .collect();
+        let mut poison = g.skills.get(Id::new(0)).unwrap().clone();
+        poison.range.max = 8;
+        poison.effects = vec![Id::new(1)].into_iter().collect();
+        g.skills.get_mut(Id::new(0)).unwrap().range.target = Target::Anything;
+        g.skills = g
+            .skills
+            .into_iter()
+            .chain(std::iter::once((Id::new(1), poison)))
+            .collect();
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (summoner, enemy) = (duel.attacker, duel.enemy);
+
+        // summons cannot stand in holes either
+        let hole = duel.toward_enemy(1);
+        g.maps.get_mut(Id::new(0)).unwrap().data[hole.raw()] =
+            serde_json::from_value(json!({ "height": 0, "attribute": "Hole" })).unwrap();
+        assert!(matches!(
+            gs.next_action(&g, Action::Skill(Id::new(0), hole)),
+            Err(Error::InvalidTarget)
+        ));
+
+        // right below the summoner
+        let cell = Id::new(duel.start.raw() + 5);
+        gs.next_action(&g, Action::Skill(Id::new(0), cell)).unwrap();
+        let totem = gs.player_at(cell).unwrap().0;
+        while gs.player_to_play() != totem {
+            gs.next_action(&g, Action::Pass).unwrap();
+        }
+        gs.next_action(&g, Action::Skill(Id::new(1), duel.enemy_cell))
+            .unwrap();
+        assert_eq!(gs.characters.get(enemy).unwrap().buffs.len(), 1);
+
+        // the poison ticks once, then goes away with the totem when the third round starts
+        skip_rounds(&g, &mut gs, summoner, 3);
+        assert!(gs.characters.get(totem).is_none());
+        let enemy = gs.characters.get(enemy).unwrap();
+        assert!(enemy.buffs.is_empty());
+        assert_eq!(enemy.current_health, 18);
+    }
+
+    #[test]
+    fn test_summoner_dies_before_summon_turn() {
+        let mut g = game_definition();
+        g.effects.get_mut(Id::new(0)).unwrap().kind = EffectKind::Summon(Summon {
+            class: Id::new(0),
+            name: "Totem".to_owned(),
+            lifespan: None,
+        });
+        g.skills.get_mut(Id::new(0)).unwrap().range.target = Target::Anything;
+
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let (summoner, enemy, cell) = (duel.attacker, duel.enemy, duel.toward_enemy(1));
+        gs.next_action(&g, Action::Skill(Id::new(0), cell)).unwrap();
+        let totem = gs.player_at(cell).unwrap().0;
+        while gs.player_to_play() != summoner {
+            gs.next_action(&g, Action::Pass).unwrap();
         }
+
+        // the totem plays right after its summoner, who dies from their poison when passing
+        gs.turn_order.retain(|id| *id != totem);
+        let summoner_pos = gs.turn_order.len() - 1;
+        gs.turn_order.insert(summoner_pos, totem);
+        let poison = Buff {
+            name: "Poison".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 3,
+            kind: BuffKind::DoT(serde_json::from_value(json!({ "flat_pure": 2 })).unwrap()),
+        };
+        let character = gs.characters.get_mut(summoner).unwrap();
+        character.current_health = 1;
+        character.buffs.push(BuffInstance::new(poison, enemy));
+
+        gs.next_action(&g, Action::Pass).unwrap();
+        assert!(!gs.characters.get(summoner).unwrap().is_alive());
+        assert!(gs.characters.get(totem).is_none());
+        assert!(!gs.turn_order.contains(&totem) && !gs.next_turn_order.contains(&totem));
+        assert_eq!(gs.player_to_play(), enemy);
     }
 }
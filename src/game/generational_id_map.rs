@@ -0,0 +1,170 @@
+use crate::game::id_map::{Id, IdMap};
+use serde::{Deserialize, Serialize};
+
+/// Map whose elements can be added and removed at any time. Slots are reused, but each reuse
+/// bumps the slot's generation: ids of removed elements are thus never valid again, even once
+/// their slot is taken by another element.
+/// Serialized as a list of [generation, element or null].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenerationalIdMap<T>(Vec<(u32, Option<T>)>);
+
+impl<T> Default for GenerationalIdMap<T> {
+    fn default() -> Self {
+        GenerationalIdMap(Vec::new())
+    }
+}
+
+impl<T> GenerationalIdMap<T> {
+    pub fn new() -> Self {
+        GenerationalIdMap::default()
+    }
+
+    pub fn insert(&mut self, t: T) -> Id<T> {
+        match self.0.iter().position(|(_, slot)| slot.is_none()) {
+            Some(index) => {
+                let (generation, slot) = &mut self.0[index];
+                *generation = generation.wrapping_add(1);
+                *slot = Some(t);
+                Id::with_generation(index, *generation)
+            }
+            None => {
+                self.0.push((0, Some(t)));
+                Id::with_generation(self.0.len() - 1, 0)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: Id<T>) -> Option<T> {
+        match self.0.get_mut(id.index()) {
+            Some((generation, slot)) if *generation == id.generation() => slot.take(),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: Id<T>) -> Option<&T> {
+        match self.0.get(id.index()) {
+            Some((generation, Some(t))) if *generation == id.generation() => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut T> {
+        match self.0.get_mut(id.index()) {
+            Some((generation, Some(t))) if *generation == id.generation() => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (generation, slot))| {
+                slot.as_ref()
+                    .map(|t| (Id::with_generation(index, *generation), t))
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Id<T>, &mut T)> {
+        self.0
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, (generation, slot))| {
+                let generation = *generation;
+                slot.as_mut()
+                    .map(move |t| (Id::with_generation(index, generation), t))
+            })
+    }
+
+    pub fn ids(&self) -> Vec<Id<T>> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+}
+
+/// Ids of the IdMap stay valid (they are of generation 0), missing ids becoming free slots
+impl<T> From<IdMap<T>> for GenerationalIdMap<T> {
+    fn from(map: IdMap<T>) -> Self {
+        let mut slots = Vec::new();
+        for (id, t) in map {
+            while slots.len() < id.raw() {
+                slots.push((0, None));
+            }
+            slots.push((0, Some(t)));
+        }
+        GenerationalIdMap(slots)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GenerationalIdMap;
+    use crate::game::id_map::{Id, IdMap};
+
+    #[test]
+    fn test_stale_ids() {
+        let mut map = GenerationalIdMap::new();
+        let first = map.insert('a');
+        let second = map.insert('b');
+
+        assert_eq!(map.remove(first), Some('a'));
+        assert_eq!(map.get(first), None);
+        assert_eq!(map.remove(first), None);
+
+        // the slot is reused, but not the id
+        let third = map.insert('c');
+        assert_eq!(third.index(), first.index());
+        assert_ne!(third, first);
+        assert_eq!(map.get(first), None);
+        assert_eq!(map.get(third), Some(&'c'));
+        assert_eq!(map.get(second), Some(&'b'));
+        assert_eq!(map.ids(), vec![third, second]);
+    }
+
+    #[test]
+    fn test_from_id_map() {
+        let map = vec![(Id::new(0), 'a'), (Id::new(2), 'c')]
+            .into_iter()
+            .collect::<IdMap<_>>();
+        let mut map = GenerationalIdMap::from(map);
+        assert_eq!(map.get(Id::new(0)), Some(&'a'));
+        assert_eq!(map.get(Id::new(1)), None);
+        assert_eq!(map.get(Id::new(2)), Some(&'c'));
+
+        let inserted = map.insert('b');
+        assert_eq!(inserted.index(), 1);
+        assert_eq!(inserted.generation(), 1);
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut map = GenerationalIdMap::new();
+        let first = map.insert(1);
+        map.insert(2);
+        map.remove(first);
+        let third = map.insert(3);
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, "[[1,3],[0,2]]");
+
+        // the generation does not depend on the size of usize
+        let id = serde_json::to_string(&third).unwrap();
+        assert_eq!(id, "4294967296");
+        assert_eq!(serde_json::from_str::<Id<i32>>(&id).unwrap(), third);
+
+        let map: GenerationalIdMap<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map.get(third), Some(&3));
+        assert_eq!(map.get(first), None);
+    }
+}
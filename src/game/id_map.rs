@@ -1,14 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{
-    hash_map::{Iter, IterMut},
-    HashMap,
+    btree_map::{IntoIter, Iter, IterMut},
+    BTreeMap,
 };
 use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
 use std::marker::PhantomData;
 
+/// Ids handed out by a GenerationalIdMap also have a generation, stored in the upper half of the
+/// serialized integer, which keeps them serialized as a single integer on every platform. Other
+/// ids always are of generation 0.
 #[derive(Serialize, Deserialize, Debug)]
-#[serde(from = "usize", into = "usize")]
-pub struct Id<T>(usize, PhantomData<T>);
+#[serde(from = "u64", into = "u64")]
+pub struct Id<T>(usize, u32, PhantomData<T>);
+
+const GENERATION_SHIFT: u32 = 32;
+const INDEX_MASK: u64 = (1 << GENERATION_SHIFT) - 1;
 
 impl<T> From<usize> for Id<T> {
     fn from(id: usize) -> Self {
@@ -16,15 +24,22 @@ impl<T> From<usize> for Id<T> {
     }
 }
 
-impl<T> From<Id<T>> for usize {
+impl<T> From<u64> for Id<T> {
+    fn from(id: u64) -> Self {
+        Id::with_generation((id & INDEX_MASK) as usize, (id >> GENERATION_SHIFT) as u32)
+    }
+}
+
+impl<T> From<Id<T>> for u64 {
     fn from(id: Id<T>) -> Self {
-        id.0
+        (u64::from(id.1) << GENERATION_SHIFT) | id.0 as u64
     }
 }
 
 impl<T> Hash for Id<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.0.hash(state);
+        self.1.hash(state);
     }
 }
 
@@ -41,23 +56,50 @@ impl<T> Clone for Id<T> {
 
 impl<T> PartialEq for Id<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        self.0 == other.0 && self.1 == other.1
     }
 }
 impl<T> Eq for Id<T> {}
 
+impl<T> PartialOrd for Id<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Id<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.1, self.0).cmp(&(other.1, other.0))
+    }
+}
+
 impl<T> Id<T> {
     pub fn new(id: usize) -> Self {
-        Id(id, PhantomData)
+        Id(id, 0, PhantomData)
     }
 
+    pub fn with_generation(index: usize, generation: u32) -> Self {
+        debug_assert!(index as u64 <= INDEX_MASK, "Id index is too big");
+        Id(index, generation, PhantomData)
+    }
+
+    /// Same as the index, the generation being only meaningful to a GenerationalIdMap
     pub fn raw(self) -> usize {
         self.0
     }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    pub fn generation(self) -> u32 {
+        self.1
+    }
 }
 
+/// Iterated (and serialized) by increasing id, so that it behaves the same in every process
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct IdMap<T>(HashMap<Id<T>, T>);
+pub struct IdMap<T>(BTreeMap<Id<T>, T>);
 
 impl<T> IdMap<T> {
     pub fn get(&self, id: Id<T>) -> Option<&T> {
@@ -77,16 +119,31 @@ impl<T> IdMap<T> {
     }
 
     pub fn ids(&self) -> Vec<Id<T>> {
-       self.0.keys().copied().collect()
+        self.0.keys().copied().collect()
+    }
+}
+
+impl<T> IntoIterator for IdMap<T> {
+    type Item = (Id<T>, T);
+    type IntoIter = IntoIter<Id<T>, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T> FromIterator<(Id<T>, T)> for IdMap<T> {
+    fn from_iter<I: IntoIterator<Item = (Id<T>, T)>>(iter: I) -> Self {
+        IdMap(iter.into_iter().collect())
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
-pub struct IdMapBuilder<T>(HashMap<Id<T>, T>, usize);
+pub struct IdMapBuilder<T>(BTreeMap<Id<T>, T>, usize);
 
 impl<T> IdMapBuilder<T> {
     pub fn new() -> Self {
-        IdMapBuilder(HashMap::new(), 0)
+        IdMapBuilder(BTreeMap::new(), 0)
     }
 
     pub fn add(&mut self, t: T) -> Id<T> {
@@ -96,7 +153,55 @@ impl<T> IdMapBuilder<T> {
         id
     }
 
+    /// Ids are never reused, removing an element simply leaves a hole
+    pub fn remove(&mut self, id: Id<T>) -> Option<T> {
+        self.0.remove(&id)
+    }
+
+    pub fn get(&self, id: Id<T>) -> Option<&T> {
+        self.0.get(&id)
+    }
+
+    pub fn iter(&self) -> Iter<'_, Id<T>, T> {
+        self.0.iter()
+    }
+
     pub fn build(self) -> IdMap<T> {
         IdMap(self.0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Id, IdMap, IdMapBuilder};
+
+    #[test]
+    fn test_iteration_order() {
+        let mut builder = IdMapBuilder::new();
+        for i in 0..20 {
+            builder.add(i);
+        }
+        builder.remove(Id::new(3));
+        let map = builder.build();
+
+        let ids = map.ids().into_iter().map(Id::raw).collect::<Vec<_>>();
+        let expected = (0..20).filter(|i| *i != 3).collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+
+        let values = map.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_serialization_order() {
+        let map = vec![(Id::new(10), 'c'), (Id::new(2), 'b'), (Id::new(0), 'a')]
+            .into_iter()
+            .collect::<IdMap<_>>();
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"{"0":"a","2":"b","10":"c"}"#);
+
+        let map: IdMap<char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&map).unwrap(), json);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::game::character::{Character, CharacterMapBuilder};
+use crate::game::class::Class;
+use crate::game::error::Error;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::GameState;
+use crate::game::id_map::Id;
+use crate::game::map::{Cell, GameMap, Team};
+use crate::game::session::{PlayerToken, Sessions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LobbyMember {
+    pub id: Id<Character>,
+    pub character: Character,
+    pub ready: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LobbyTeam {
+    pub spots_left: usize,
+    pub free_starting_cells: Vec<Id<Cell>>,
+    pub members: Vec<LobbyMember>,
+}
+
+/// Snapshot of a lobby, as shown to the players choosing their slot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LobbyState {
+    pub map: Id<GameMap>,
+    pub team_size: usize,
+    pub teams: Vec<LobbyTeam>,
+}
+
+/// Game being created: players pick a team and a starting cell, then mark themselves as ready.
+/// The game starts once every slot is taken and every player is ready.
+#[derive(Debug, Clone)]
+pub struct Lobby<'a> {
+    builder: CharacterMapBuilder<'a>,
+    game_definition: &'a GameDefinition,
+    map: Id<GameMap>,
+    team_size: usize,
+    ready: HashSet<Id<Character>>,
+    sessions: Sessions,
+}
+
+impl<'a> Lobby<'a> {
+    pub fn new(
+        game_definition: &'a GameDefinition,
+        map: Id<GameMap>,
+        team_size: usize,
+    ) -> Result<Self, Error> {
+        let teams = &game_definition
+            .maps
+            .get(map)
+            .ok_or(Error::InvalidMap)?
+            .teams;
+        // an empty lobby would start right away, with nobody to play
+        if team_size == 0 || teams.is_empty() {
+            return Err(Error::InvalidTeamSize);
+        }
+
+        Ok(Lobby {
+            builder: CharacterMapBuilder::new(game_definition, map, team_size),
+            game_definition,
+            map,
+            team_size,
+            ready: HashSet::new(),
+            sessions: Sessions::new(),
+        })
+    }
+
+    pub fn map(&self) -> Id<GameMap> {
+        self.map
+    }
+
+    pub fn team_size(&self) -> usize {
+        self.team_size
+    }
+
+    pub fn spots_left(&self) -> usize {
+        (0..self.builder.num_teams())
+            .filter_map(|team| self.builder.spots_left(Id::new(team)))
+            .sum()
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    /// Players joining for the first time are not expected to have a token yet, and receive one
+    /// along with their character id
+    pub fn join<S: Into<String>>(
+        &mut self,
+        token: Option<&PlayerToken>,
+        name: S,
+        class: Id<Class>,
+        team: Id<Team>,
+        position: Id<Cell>,
+    ) -> Result<(Id<Character>, PlayerToken), Error> {
+        if let Some(token) = token {
+            if !self.sessions.is_known(token) {
+                return Err(Error::InvalidToken);
+            }
+        }
+
+        let class_data = self
+            .game_definition
+            .classes
+            .get(class)
+            .ok_or(Error::InvalidCharacterClass)?;
+        let id = self
+            .builder
+            .add(Character::new(class, position, class_data, name, team))?;
+        let token = self.sessions.register(token, id)?;
+        Ok((id, token))
+    }
+
+    pub fn leave(&mut self, token: &PlayerToken, id: Id<Character>) -> Result<Character, Error> {
+        self.sessions.check_owner(token, id)?;
+        let character = self.builder.remove(id)?;
+        self.sessions.unregister(id);
+        self.ready.remove(&id);
+        Ok(character)
+    }
+
+    pub fn set_ready(
+        &mut self,
+        token: &PlayerToken,
+        id: Id<Character>,
+        ready: bool,
+    ) -> Result<(), Error> {
+        self.sessions.check_owner(token, id)?;
+
+        if ready {
+            self.ready.insert(id);
+        } else {
+            self.ready.remove(&id);
+        }
+        Ok(())
+    }
+
+    pub fn can_start(&self) -> bool {
+        self.builder.can_build()
+            && self
+                .builder
+                .characters()
+                .all(|(id, _)| self.ready.contains(id))
+    }
+
+    pub fn state(&self) -> LobbyState {
+        let teams = (0..self.builder.num_teams())
+            .map(Id::new)
+            .map(|team| LobbyTeam {
+                spots_left: self.builder.spots_left(team).unwrap_or(0),
+                free_starting_cells: self
+                    .builder
+                    .free_starting_cells(team)
+                    .map(|cells| cells.to_vec())
+                    .unwrap_or_default(),
+                members: self
+                    .builder
+                    .characters()
+                    .filter(|(_, character)| character.team == team)
+                    .map(|(id, character)| LobbyMember {
+                        id: *id,
+                        character: character.clone(),
+                        ready: self.ready.contains(id),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        LobbyState {
+            map: self.map,
+            team_size: self.team_size,
+            teams,
+        }
+    }
+
+    /// The sessions are handed over to the running game, so that players keep their characters
+    #[allow(clippy::result_large_err)]
+    pub fn try_start(self) -> Result<(GameState, Sessions), Self> {
+        if !self.can_start() {
+            return Err(self);
+        }
+
+        let g = self.game_definition;
+        let map = self.map;
+        Ok((GameState::new(g, self.builder.build(), map), self.sessions))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lobby;
+    use crate::game::error::Error;
+    use crate::game::id_map::Id;
+    use crate::game::session::PlayerToken;
+    use crate::game::test_utils::game_definition;
+
+    #[test]
+    fn test_lobby_slots() {
+        let mut g = game_definition();
+        assert!(matches!(
+            Lobby::new(&g, Id::new(0), 0),
+            Err(Error::InvalidTeamSize)
+        ));
+        assert!(matches!(
+            Lobby::new(&g, Id::new(1), 1),
+            Err(Error::InvalidMap)
+        ));
+        let mut no_teams = g.maps.get(Id::new(0)).unwrap().clone();
+        no_teams.teams.clear();
+        g.maps = g
+            .maps
+            .into_iter()
+            .chain(std::iter::once((Id::new(1), no_teams)))
+            .collect();
+        assert!(matches!(
+            Lobby::new(&g, Id::new(1), 1),
+            Err(Error::InvalidTeamSize)
+        ));
+
+        let mut lobby = Lobby::new(&g, Id::new(0), 1).unwrap();
+        assert_eq!(lobby.spots_left(), 2);
+
+        let (first, token) = lobby
+            .join(None, "first", Id::new(0), Id::new(0), Id::new(0))
+            .unwrap();
+        assert!(matches!(
+            lobby.join(None, "second", Id::new(0), Id::new(0), Id::new(20)),
+            Err(Error::TeamFull)
+        ));
+        assert!(matches!(
+            lobby.join(None, "second", Id::new(0), Id::new(2), Id::new(4)),
+            Err(Error::InvalidTeam)
+        ));
+        assert!(matches!(
+            lobby.join(None, "second", Id::new(0), Id::new(1), Id::new(0)),
+            Err(Error::InvalidStartingCell)
+        ));
+
+        lobby.leave(&token, first).unwrap();
+        let state = lobby.state();
+        assert_eq!(state.teams[0].spots_left, 1);
+        assert_eq!(state.teams[0].free_starting_cells.len(), 2);
+        assert!(state.teams[0].members.is_empty());
+    }
+
+    #[test]
+    fn test_lobby_ownership() {
+        let g = game_definition();
+        let mut lobby = Lobby::new(&g, Id::new(0), 2).unwrap();
+
+        let (first, token) = lobby
+            .join(None, "first", Id::new(0), Id::new(0), Id::new(0))
+            .unwrap();
+        let (second, same_token) = lobby
+            .join(Some(&token), "second", Id::new(0), Id::new(0), Id::new(20))
+            .unwrap();
+        let (third, other_token) = lobby
+            .join(None, "third", Id::new(0), Id::new(1), Id::new(4))
+            .unwrap();
+        assert_eq!(token, same_token);
+        assert_ne!(token, other_token);
+
+        assert!(matches!(
+            lobby.set_ready(&token, third, true),
+            Err(Error::NotCharacterOwner)
+        ));
+        assert!(matches!(
+            lobby.leave(&PlayerToken::generate(), first),
+            Err(Error::InvalidToken)
+        ));
+
+        let mut owned = lobby.sessions().characters_of(&token).unwrap();
+        owned.sort_by_key(|id| id.raw());
+        assert_eq!(owned, vec![first, second]);
+
+        // leaving every character does not forget the player
+        lobby.leave(&other_token, third).unwrap();
+        assert_eq!(
+            lobby.sessions().characters_of(&other_token).unwrap(),
+            vec![]
+        );
+        let (_, rejoined) = lobby
+            .join(
+                Some(&other_token),
+                "fourth",
+                Id::new(0),
+                Id::new(1),
+                Id::new(4),
+            )
+            .unwrap();
+        assert_eq!(rejoined, other_token);
+    }
+
+    #[test]
+    fn test_lobby_start() {
+        let g = game_definition();
+        let mut lobby = Lobby::new(&g, Id::new(0), 1).unwrap();
+
+        let (first, first_token) = lobby
+            .join(None, "first", Id::new(0), Id::new(0), Id::new(0))
+            .unwrap();
+        let (second, second_token) = lobby
+            .join(None, "second", Id::new(0), Id::new(1), Id::new(4))
+            .unwrap();
+        lobby.set_ready(&first_token, first, true).unwrap();
+        let mut lobby = lobby.try_start().unwrap_err();
+
+        lobby.set_ready(&second_token, second, true).unwrap();
+        let (gs, sessions) = lobby.try_start().unwrap();
+        assert_eq!(gs.turn_order.len(), 2);
+
+        let to_play = gs.player_to_play();
+        let (owner, other) = if to_play == first {
+            (first_token, second_token)
+        } else {
+            (second_token, first_token)
+        };
+        assert!(sessions.check_action(&owner, &gs).is_ok());
+        assert!(matches!(
+            sessions.check_action(&other, &gs),
+            Err(Error::NotCharacterOwner)
+        ));
+    }
+}
@@ -11,7 +11,7 @@ pub struct Team(pub String, pub Vec<Id<Cell>>);
 
 impl Id<Cell> {
     fn invalid() -> Id<Cell> {
-        Id::new(std::usize::MAX)
+        Id::new(usize::MAX)
     }
 }
 
@@ -30,6 +30,30 @@ pub struct Cell {
     attribute: CellAttibute,
 }
 
+impl Cell {
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn blocks_sight(&self) -> bool {
+        matches!(self.attribute, CellAttibute::Wall)
+    }
+
+    pub fn blocks_movement(&self) -> bool {
+        matches!(self.attribute, CellAttibute::Wall)
+    }
+
+    pub fn is_hole(&self) -> bool {
+        matches!(self.attribute, CellAttibute::Hole)
+    }
+
+    /// Whether a character can walk or be put on the cell. Only forced movement sends characters
+    /// into holes.
+    pub fn can_stand_on(&self) -> bool {
+        !self.blocks_movement() && !self.is_hole()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameMap {
     pub name: String,
@@ -138,7 +162,42 @@ impl GameMap {
         ]
     }
 
-    fn is_valid_cell(&self, c: Id<Cell>) -> bool {
+    /// Cell next to the given one in the (dx, dy) direction, if it is inside the map
+    pub fn neighbor(&self, c: Id<Cell>, dx: i32, dy: i32) -> Option<Id<Cell>> {
+        let (x, y) = self.id_to_xy_i32(c);
+        let (x, y) = (x + dx, y + dy);
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            None
+        } else {
+            Some(self.xy_to_id(x as usize, y as usize))
+        }
+    }
+
+    /// Unit step going from one cell towards the other: along the main axis, or diagonally if
+    /// both axes are equally far
+    pub fn direction(&self, from: Id<Cell>, to: Id<Cell>) -> (i32, i32) {
+        let (fx, fy) = self.id_to_xy_i32(from);
+        let (tx, ty) = self.id_to_xy_i32(to);
+        let (dx, dy) = (tx - fx, ty - fy);
+
+        match dx.abs().cmp(&dy.abs()) {
+            Ordering::Greater => (dx.signum(), 0),
+            Ordering::Less => (0, dy.signum()),
+            Ordering::Equal => (dx.signum(), dy.signum()),
+        }
+    }
+
+    /// Whether a wall next to the target stands between it and the attacker
+    pub fn has_cover(&self, target: Id<Cell>, attacker: Id<Cell>) -> bool {
+        let distance = self.distance(target, attacker);
+        self.surrounding_cells(target).iter().any(|cell| {
+            self.is_valid_cell(*cell)
+                && self[*cell].blocks_movement()
+                && self.distance(*cell, attacker) < distance
+        })
+    }
+
+    pub fn is_valid_cell(&self, c: Id<Cell>) -> bool {
         self.data.len() > c.raw()
     }
 
@@ -148,7 +207,50 @@ impl GameMap {
         ((ax as i32 - bx as i32).abs() + (ay as i32 - by as i32).abs()) as u32
     }
 
+    /// Walks the grid line between the centers of both cells (Bresenham), the line of sight being
+    /// blocked if any cell in between blocks sight. The end cells themselves are never checked.
+    pub fn has_line_of_sight(&self, start: Id<Cell>, end: Id<Cell>) -> bool {
+        let (mut x, mut y) = self.id_to_xy_i32(start);
+        let (ex, ey) = self.id_to_xy_i32(end);
+
+        let dx = (ex - x).abs();
+        let dy = -(ey - y).abs();
+        let sx = if x < ex { 1 } else { -1 };
+        let sy = if y < ey { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        if start == end {
+            return true;
+        }
+
+        loop {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+
+            if x == ex && y == ey {
+                return true;
+            }
+
+            if self[self.xy_to_id(x as usize, y as usize)].blocks_sight() {
+                return false;
+            }
+        }
+    }
+
     pub fn can_move_to(&self, start: Id<Cell>, end: Id<Cell>, swiftness: i32) -> bool {
+        self.path_cost(start, end, swiftness).is_some()
+    }
+
+    /// Cost of the cheapest path between the two cells, or None if it exceeds max_cost. The path
+    /// goes around walls and holes.
+    pub fn path_cost(&self, start: Id<Cell>, end: Id<Cell>, max_cost: i32) -> Option<i32> {
         let mut nodes = BinaryHeap::new();
         nodes.push(Reverse(Node {
             real_cost: 0,
@@ -161,20 +263,20 @@ impl GameMap {
                 n.0
             } else {
                 // No path possible (should never happen though...)
-                return false;
+                return None;
             };
 
             // our two exit conditions (arrived, or too long)
-            if swiftness < n.cost() as i32 {
-                return false;
+            if max_cost < n.cost() as i32 {
+                return None;
             } else if end == n.curr_cell {
-                return true;
+                return Some(n.real_cost as i32);
             }
 
             let prev_cell = &self[n.curr_cell];
             for neighbor in self.surrounding_cells(n.curr_cell).iter() {
                 let neighbor = *neighbor;
-                if !self.is_valid_cell(neighbor) {
+                if !self.is_valid_cell(neighbor) || !self[neighbor].can_stand_on() {
                     continue;
                 }
 
@@ -388,5 +490,68 @@ mod test {
         let end = Id::new(4);
         assert!(map.can_move_to(start, end, 4));
         assert!(!map.can_move_to(start, end, 3));
+        assert_eq!(map.path_cost(start, end, 10), Some(4));
+    }
+
+    #[test]
+    fn test_a_star_obstacles() {
+        let width = 5;
+        let height = 5;
+        let mut data = vec![
+            Cell {
+                height: 0,
+                attribute: CellAttibute::None,
+            };
+            25
+        ];
+        data[2].attribute = CellAttibute::Wall;
+        data[7].attribute = CellAttibute::Hole;
+        let map = GameMap {
+            name: "".to_owned(),
+            width,
+            height,
+            data,
+            teams: Default::default(),
+        };
+
+        // around the wall and the hole below it
+        let start = Id::new(0);
+        let end = Id::new(4);
+        assert_eq!(map.path_cost(start, end, 10), Some(8));
+        assert!(!map.can_move_to(start, Id::new(2), 10));
+        assert!(!map.can_move_to(start, Id::new(7), 10));
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let width = 5;
+        let height = 5;
+        let mut data = vec![
+            Cell {
+                height: 0,
+                attribute: CellAttibute::None,
+            };
+            25
+        ];
+        data[12].attribute = CellAttibute::Wall;
+        let map = GameMap {
+            name: "".to_owned(),
+            width,
+            height,
+            data,
+            teams: Default::default(),
+        };
+
+        // straight through the wall in the middle
+        assert!(!map.has_line_of_sight(map.xy_to_id(0, 2), map.xy_to_id(4, 2)));
+        assert!(!map.has_line_of_sight(map.xy_to_id(0, 0), map.xy_to_id(4, 4)));
+
+        // next to it
+        assert!(map.has_line_of_sight(map.xy_to_id(0, 1), map.xy_to_id(4, 1)));
+        assert!(map.has_line_of_sight(map.xy_to_id(4, 0), map.xy_to_id(4, 4)));
+
+        // the wall itself can be seen
+        assert!(map.has_line_of_sight(map.xy_to_id(0, 2), map.xy_to_id(2, 2)));
+        assert!(map.has_line_of_sight(map.xy_to_id(3, 3), map.xy_to_id(3, 3)));
     }
 }
@@ -4,6 +4,9 @@ pub use error::Error;
 mod id_map;
 pub use id_map::{Id, IdMap, IdMapBuilder};
 
+mod generational_id_map;
+pub use generational_id_map::GenerationalIdMap;
+
 mod character;
 pub use character::{Character, CharacterMapBuilder};
 
@@ -16,14 +19,59 @@ pub use game_state::{Action, GameState};
 mod class;
 pub use class::Class;
 
+mod combat;
+pub use combat::{CombatModifiers, SkillPreview};
+
 mod damage;
-pub use damage::Damage;
+pub use damage::{
+    Damage, DamageBreakdown, DamagePart, DamagePreview, DamageRoll, DamageType, Scaling,
+    ScalingSource,
+};
 
 mod effect;
 pub use effect::Effect;
 
+mod event;
+pub use event::Event;
+
+mod lobby;
+pub use lobby::{Lobby, LobbyMember, LobbyState, LobbyTeam};
+
 mod map;
 pub use map::{Cell, GameMap, Team};
 
+mod replay;
+pub use replay::{Replay, ReplayPlayer, REPLAY_VERSION};
+
+mod passive;
+pub use passive::{Passive, PassiveTarget, Trigger};
+
+mod reaction;
+pub use reaction::{Reaction, ReactionTrigger};
+
+mod rng;
+pub use rng::GameRng;
+
+mod rules;
+pub use rules::{CritRules, Initiative, Rules, TerrainRules, TurnModel};
+
+mod script;
+pub use script::{Script, ScriptAction, ScriptContext};
+
+mod save;
+pub use save::{SaveGame, SAVE_VERSION};
+
+mod session;
+pub use session::{PlayerToken, Sessions};
+
 mod skill;
 pub use skill::Skill;
+
+mod undo;
+pub use undo::UndoHistory;
+
+mod view;
+pub use view::{BuffView, CharacterView, FogOfWar, GameStateView, TurnSlot};
+
+#[cfg(test)]
+pub(crate) mod test_utils;
@@ -0,0 +1,37 @@
+use crate::game::effect::Effect;
+use crate::game::id_map::Id;
+use serde::{Deserialize, Serialize};
+
+/// What a passive reacts to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// The owner starts playing
+    TurnStart,
+    /// The owner is damaged by another character
+    Hit,
+    /// The owner kills another character
+    Kill,
+    /// Another character of the owner's team dies
+    AllyDeath,
+    /// The owner moves next to an enemy who was not next to them before
+    AdjacentToEnemy,
+}
+
+/// Who the effects of a passive apply to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassiveTarget {
+    Owner,
+    /// The attacker, the killed character, the dead ally or the enemy, depending on the trigger.
+    /// There is no such character when the owner's turn starts, so these passives do nothing.
+    Other,
+}
+
+/// Ability of a class which is not used like a skill, but applies its effects whenever the
+/// trigger happens
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Passive {
+    pub name: String,
+    pub trigger: Trigger,
+    pub target: PassiveTarget,
+    pub effects: Vec<Id<Effect>>,
+}
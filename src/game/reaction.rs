@@ -0,0 +1,25 @@
+use crate::game::id_map::Id;
+use crate::game::passive::PassiveTarget;
+use crate::game::skill::Skill;
+use serde::{Deserialize, Serialize};
+
+/// What makes a character act outside of their turn
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReactionTrigger {
+    /// An adjacent enemy moves away, before they leave their cell (opportunity attack)
+    EnemyLeaves,
+    /// An enemy uses a skill on the character, before its effects are applied (e.g. parry)
+    Targeted,
+    /// Same thing, once the effects have been applied (e.g. counter-attack)
+    Attacked,
+}
+
+/// Skill a class uses automatically when the trigger happens, as long as it could use it during
+/// its own turn (range, cooldown etc.) and has reactions left for the round. The other character
+/// is the enemy who moved away or used their skill.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reaction {
+    pub trigger: ReactionTrigger,
+    pub target: PassiveTarget,
+    pub skill: Id<Skill>,
+}
@@ -0,0 +1,242 @@
+use crate::game::error::Error;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::{Action, GameState};
+use crate::game::rng::GameRng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Bumped whenever the format of replays changes in an incompatible way
+pub const REPLAY_VERSION: u32 = 2;
+
+/// Number of actions between two snapshots when playing a replay back
+const SNAPSHOT_INTERVAL: usize = 16;
+
+/// A game recorded from a given state: replaying the same actions with the same RNG always leads
+/// to the same states
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub version: u32,
+    pub definition_fingerprint: u64,
+    pub initial: GameState,
+    /// State of the game's RNG when the recording started, which seeds every roll of the replay
+    pub rng: GameRng,
+    pub actions: Vec<Action>,
+}
+
+impl Replay {
+    pub fn new(g: &GameDefinition, initial: &GameState) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            definition_fingerprint: g.fingerprint(),
+            initial: initial.clone(),
+            rng: initial.rng.clone(),
+            actions: Vec::new(),
+        }
+    }
+
+    /// Plays the action, and records it if it was valid
+    pub fn next_action(
+        &mut self,
+        gs: &mut GameState,
+        g: &GameDefinition,
+        action: Action,
+    ) -> Result<bool, Error> {
+        let new_turn = gs.next_action(g, action.clone())?;
+        self.actions.push(action);
+        Ok(new_turn)
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> io::Result<Replay> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        Replay::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn check_compatibility(&self, g: &GameDefinition) -> Result<(), Error> {
+        if self.version != REPLAY_VERSION {
+            return Err(Error::UnsupportedSaveVersion);
+        }
+
+        if self.definition_fingerprint != g.fingerprint() {
+            return Err(Error::IncompatibleGameDefinition);
+        }
+
+        Ok(())
+    }
+
+    fn initial_state(&self) -> GameState {
+        let mut gs = self.initial.clone();
+        gs.rng = self.rng.clone();
+        gs
+    }
+}
+
+/// Steps through a replay. Going backward restores the closest snapshot before the wanted
+/// position, and plays the actions again from there.
+#[derive(Debug)]
+pub struct ReplayPlayer<'a> {
+    g: &'a GameDefinition,
+    replay: Replay,
+    state: GameState,
+    position: usize,
+    /// snapshots[i] is the state after i * SNAPSHOT_INTERVAL actions
+    snapshots: Vec<GameState>,
+}
+
+impl<'a> ReplayPlayer<'a> {
+    pub fn new(g: &'a GameDefinition, replay: Replay) -> Result<Self, Error> {
+        replay.check_compatibility(g)?;
+
+        let state = replay.initial_state();
+        Ok(ReplayPlayer {
+            g,
+            snapshots: vec![state.clone()],
+            replay,
+            state,
+            position: 0,
+        })
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    /// Number of actions played so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.actions.is_empty()
+    }
+
+    /// Returns false if the end of the replay was already reached
+    pub fn step_forward(&mut self) -> Result<bool, Error> {
+        let action = match self.replay.actions.get(self.position) {
+            Some(action) => action.clone(),
+            None => return Ok(false),
+        };
+
+        self.state.next_action(self.g, action)?;
+        self.position += 1;
+
+        if self.position == self.snapshots.len() * SNAPSHOT_INTERVAL {
+            self.snapshots.push(self.state.clone());
+        }
+        Ok(true)
+    }
+
+    /// Returns false if the beginning of the replay was already reached
+    pub fn step_backward(&mut self) -> Result<bool, Error> {
+        if self.position == 0 {
+            return Ok(false);
+        }
+        self.seek(self.position - 1)?;
+        Ok(true)
+    }
+
+    pub fn seek(&mut self, position: usize) -> Result<(), Error> {
+        let position = std::cmp::min(position, self.len());
+        if position < self.position {
+            let snapshot = std::cmp::min(position / SNAPSHOT_INTERVAL, self.snapshots.len() - 1);
+            self.state = self.snapshots[snapshot].clone();
+            self.position = snapshot * SNAPSHOT_INTERVAL;
+        }
+
+        while self.position < position {
+            self.step_forward()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Replay, ReplayPlayer, SNAPSHOT_INTERVAL};
+    use crate::game::game_definition::GameDefinition;
+    use crate::game::game_state::{Action, GameState};
+    use crate::game::id_map::Id;
+    use crate::game::rules::CritRules;
+    use crate::game::test_utils::{game_definition, running_game, Duel};
+    use serde_json::{json, Value};
+
+    fn snapshot(gs: &GameState) -> Value {
+        serde_json::to_value(gs).unwrap()
+    }
+
+    /// Slashes which can miss, crit and whose damage varies, on characters who survive them all
+    fn random_definition() -> GameDefinition {
+        let mut g = game_definition();
+        g.classes.get_mut(Id::new(0)).unwrap().health = 1000;
+        g.skills.get_mut(Id::new(0)).unwrap().precision = Some(0.5);
+        g.effects.get_mut(Id::new(0)).unwrap().kind = serde_json::from_value(json!({
+            "DirectDamage": { "range": null, "damage": { "flat_physical": 6, "variance": 0.5 } }
+        }))
+        .unwrap();
+        g.rules.crits = Some(CritRules::default());
+        g
+    }
+
+    #[test]
+    fn test_replay() {
+        let g = random_definition();
+        let mut gs = running_game(&g);
+        let mut replay = Replay::new(&g, &gs);
+        let mut states = vec![snapshot(&gs)];
+
+        let next_to_enemy = Duel::new(&gs).toward_enemy(3);
+        replay
+            .next_action(&mut gs, &g, Action::Movement(next_to_enemy))
+            .unwrap();
+        states.push(snapshot(&gs));
+
+        for _ in 0..2 * SNAPSHOT_INTERVAL + 3 {
+            let to_play = gs.player_to_play();
+            let (_, enemy) = gs.characters.iter().find(|(id, _)| *id != to_play).unwrap();
+            let slash = Action::Skill(Id::new(0), enemy.position);
+            replay.next_action(&mut gs, &g, slash).unwrap();
+            states.push(snapshot(&gs));
+        }
+        assert!(gs
+            .characters
+            .iter()
+            .any(|(_, character)| character.current_health < 1000));
+
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let replay = Replay::read(bytes.as_slice()).unwrap();
+
+        let mut player = ReplayPlayer::new(&g, replay).unwrap();
+        while player.step_forward().unwrap() {
+            assert_eq!(snapshot(player.state()), states[player.position()]);
+        }
+        assert_eq!(player.position(), states.len() - 1);
+
+        while player.step_backward().unwrap() {
+            assert_eq!(snapshot(player.state()), states[player.position()]);
+        }
+        assert_eq!(player.position(), 0);
+
+        player.seek(SNAPSHOT_INTERVAL + 1).unwrap();
+        assert_eq!(snapshot(player.state()), states[SNAPSHOT_INTERVAL + 1]);
+    }
+}
@@ -0,0 +1,54 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+/// Seeded RNG used for everything random happening in a game, so that its state can be saved
+/// and replayed. It also counts how many values were drawn, which tells whether an action revealed
+/// random information.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameRng {
+    rng: Pcg32,
+    draws: u64,
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng {
+            rng: Pcg32::seed_from_u64(seed),
+            draws: 0,
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        GameRng {
+            rng: Pcg32::from_entropy(),
+            draws: 0,
+        }
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.draws += 1;
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.draws += 1;
+        self.rng.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,440 @@
+use crate::game::character::Character;
+use crate::game::class::Class;
+use crate::game::combat::CombatModifiers;
+use crate::game::damage::DamageType;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::TurnState;
+use crate::game::generational_id_map::GenerationalIdMap;
+use crate::game::id_map::Id;
+use crate::game::map::{Cell, GameMap, Team};
+use crate::game::rng::GameRng;
+use crate::game::skill::Skill;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Size of the die rolled by every character in the random initiative mode
+const INITIATIVE_DIE: i32 = 20;
+/// Size of the die rolled by characters with the same initiative score
+const TIE_BREAKER_DIE: i32 = 6;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Initiative {
+    /// Fastest characters play first
+    #[default]
+    Swiftness,
+    /// Every round, characters roll a die and add their swiftness to it
+    Random,
+}
+
+impl Initiative {
+    /// Characters are ordered by their initiative score. Characters with the same score roll a
+    /// small die to break the tie, then the ones still tied alternate the teams (starting with a
+    /// team other than the one of the previous character), then are ordered by id. Nothing is
+    /// rolled when no characters are tied in the swiftness mode, so that the order of the next
+    /// round does not depend on the RNG.
+    /// The order returned is the order in which the characters will play.
+    pub fn roll(
+        self,
+        g: &GameDefinition,
+        characters: &GenerationalIdMap<Character>,
+        rng: &mut GameRng,
+    ) -> Vec<Id<Character>> {
+        let mut rolls = characters
+            .iter()
+            .filter(|(_, character)| character.is_alive())
+            .map(|(id, character)| {
+                let class = g.classes.get(character.class).expect("Invalid class id");
+                (id, character.team, character.effective_swiftness(class))
+            })
+            .collect::<Vec<_>>();
+        // the rolls must not depend on the characters' iteration order
+        rolls.sort_by_key(|(id, _, _)| *id);
+
+        let mut rolls = rolls
+            .into_iter()
+            .map(|(id, team, swiftness)| {
+                let score = match self {
+                    Initiative::Swiftness => swiftness,
+                    Initiative::Random => swiftness + rng.gen_range(1, INITIATIVE_DIE + 1),
+                };
+                (score, (team, id))
+            })
+            .collect::<Vec<_>>();
+        rolls.sort_by_key(|(score, (_, id))| (Reverse(*score), *id));
+
+        let mut order = Vec::with_capacity(rolls.len());
+        for tied in Initiative::group_ties(rolls) {
+            let mut rolls = tied
+                .into_iter()
+                .map(|member| (0, member))
+                .collect::<Vec<_>>();
+            if rolls.len() > 1 {
+                for (tie_breaker, _) in &mut rolls {
+                    *tie_breaker = rng.gen_range(1, TIE_BREAKER_DIE + 1);
+                }
+                rolls.sort_by_key(|(tie_breaker, (_, id))| (Reverse(*tie_breaker), *id));
+            }
+
+            for still_tied in Initiative::group_ties(rolls) {
+                // characters sorted by id, grouped by team
+                let mut by_team = BTreeMap::<Id<Team>, VecDeque<Id<Character>>>::new();
+                for (team, id) in still_tied {
+                    by_team.entry(team).or_default().push_back(id);
+                }
+
+                let previous_team = order
+                    .last()
+                    .and_then(|id| characters.get(*id))
+                    .map(|character: &Character| character.team);
+                Initiative::alternate_teams(by_team, previous_team, characters, &mut order);
+            }
+        }
+        order
+    }
+
+    /// Splits values sorted by key into the groups of values sharing the same key
+    fn group_ties<T>(sorted: Vec<(i32, T)>) -> Vec<Vec<T>> {
+        let mut groups: Vec<(i32, Vec<T>)> = Vec::new();
+        for (key, value) in sorted {
+            match groups.last_mut() {
+                Some((last, group)) if *last == key => group.push(value),
+                _ => groups.push((key, vec![value])),
+            }
+        }
+        groups.into_iter().map(|(_, group)| group).collect()
+    }
+
+    fn alternate_teams(
+        mut tied: BTreeMap<Id<Team>, VecDeque<Id<Character>>>,
+        mut previous_team: Option<Id<Team>>,
+        characters: &GenerationalIdMap<Character>,
+        order: &mut Vec<Id<Character>>,
+    ) {
+        while !tied.is_empty() {
+            // next team after the previous one, wrapping around
+            let team = previous_team
+                .and_then(|previous| tied.keys().find(|team| **team > previous).copied())
+                .or_else(|| tied.keys().next().copied())
+                .expect("No team left");
+
+            let ids = tied.get_mut(&team).expect("Invalid team id");
+            let id = ids.pop_front().expect("Empty team");
+            if ids.is_empty() {
+                tied.remove(&team);
+            }
+
+            order.push(id);
+            previous_team = characters.get(id).map(|character| character.team);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TurnModel {
+    /// Characters can move once, then use a skill, which ends their turn
+    #[default]
+    TwoPhase,
+    /// Characters spend action points on skills and movement points (their swiftness) on moves,
+    /// in any order, until they pass or run out of points
+    ActionPoints,
+}
+
+impl TurnModel {
+    /// State of the turn when a character starts playing
+    pub fn start_turn(self, character: &Character, class: &Class) -> TurnState {
+        match self {
+            TurnModel::TwoPhase => TurnState::MoveOrAction,
+            TurnModel::ActionPoints => TurnState::Points {
+                action: character.effective_action_points(class),
+                movement: character.effective_swiftness(class),
+            },
+        }
+    }
+}
+
+/// How the terrain affects skills. Height differences are counted from the attacker's point of
+/// view, i.e. negative values when attacking upward.
+/// Every modifier is 0 by default, the terrain only mattering to definitions opting in
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TerrainRules {
+    pub precision_per_height: f32,
+    pub damage_per_height: f32,
+    /// Added to the max range of ranged skills for each level above the targeted cell
+    pub range_per_height: u32,
+    /// Part of the physical damage of ranged skills absorbed by a wall between both characters
+    pub cover_damage_reduction: f32,
+}
+
+impl TerrainRules {
+    pub fn modifiers(
+        &self,
+        map: &GameMap,
+        attacker: Id<Cell>,
+        target: Id<Cell>,
+        skill: &Skill,
+    ) -> CombatModifiers {
+        let height_difference = map[attacker].height() - map[target].height();
+        let cover = skill.is_ranged() && map.has_cover(target, attacker);
+
+        CombatModifiers {
+            height_difference,
+            precision: self.precision_per_height * height_difference as f32,
+            damage: f32::max(0.0, 1.0 + self.damage_per_height * height_difference as f32),
+            cover,
+            physical_damage: if cover {
+                1.0 - self.cover_damage_reduction
+            } else {
+                1.0
+            },
+        }
+    }
+
+    /// Extra max range of the skill; attacking upward never reduces it
+    pub fn range_bonus(
+        &self,
+        map: &GameMap,
+        attacker: Id<Cell>,
+        target: Id<Cell>,
+        skill: &Skill,
+    ) -> u32 {
+        let height_difference = map[attacker].height() - map[target].height();
+        if skill.is_ranged() && height_difference > 0 {
+            height_difference as u32 * self.range_per_height
+        } else {
+            0
+        }
+    }
+}
+
+/// Critical strikes multiply the physical or magical part of some damage. Their chance and
+/// multiplier grow with the attacker's dexterity (physical) or concentration (magical).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CritRules {
+    pub chance_per_point: f32,
+    pub max_chance: f32,
+    pub base_multiplier: f32,
+    pub multiplier_per_point: f32,
+}
+
+impl Default for CritRules {
+    fn default() -> Self {
+        CritRules {
+            chance_per_point: 0.02,
+            max_chance: 0.5,
+            base_multiplier: 1.5,
+            multiplier_per_point: 0.02,
+        }
+    }
+}
+
+impl CritRules {
+    fn points(attacker: &Character, class: &Class, damage_type: DamageType) -> Option<i32> {
+        match damage_type {
+            DamageType::Physical => Some(attacker.effective_dexterity(class)),
+            DamageType::Magical => Some(attacker.effective_concentration(class)),
+            DamageType::Pure => None,
+        }
+    }
+
+    /// Pure damage never crits
+    pub fn chance(&self, attacker: &Character, class: &Class, damage_type: DamageType) -> f32 {
+        CritRules::points(attacker, class, damage_type).map_or(0.0, |points| {
+            (self.chance_per_point * points as f32).clamp(0.0, self.max_chance)
+        })
+    }
+
+    pub fn multiplier(&self, attacker: &Character, class: &Class, damage_type: DamageType) -> f32 {
+        CritRules::points(attacker, class, damage_type).map_or(1.0, |points| {
+            self.base_multiplier + self.multiplier_per_point * points as f32
+        })
+    }
+}
+
+/// Optional game mechanics, shared by every game using the same definition
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rules {
+    #[serde(default)]
+    pub initiative: Initiative,
+    #[serde(default)]
+    pub turn_model: TurnModel,
+    /// Damage taken by characters forced down a cell, for each height level above the safe one
+    #[serde(default = "Rules::default_fall_damage_per_height")]
+    pub fall_damage_per_height: i32,
+    #[serde(default = "Rules::default_safe_fall_height")]
+    pub safe_fall_height: i32,
+    #[serde(default)]
+    pub terrain: TerrainRules,
+    /// No critical strikes if None
+    #[serde(default)]
+    pub crits: Option<CritRules>,
+    /// Passives triggered by the effects of passives triggered by (...) an action are not applied
+    /// past this depth, so that they cannot trigger each other forever
+    #[serde(default = "Rules::default_max_passive_depth")]
+    pub max_passive_depth: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            initiative: Initiative::default(),
+            turn_model: TurnModel::default(),
+            fall_damage_per_height: Rules::default_fall_damage_per_height(),
+            safe_fall_height: Rules::default_safe_fall_height(),
+            terrain: TerrainRules::default(),
+            crits: None,
+            max_passive_depth: Rules::default_max_passive_depth(),
+        }
+    }
+}
+
+impl Rules {
+    fn default_fall_damage_per_height() -> i32 {
+        3
+    }
+
+    fn default_safe_fall_height() -> i32 {
+        1
+    }
+
+    fn default_max_passive_depth() -> u32 {
+        3
+    }
+
+    pub fn fall_damage(&self, height: i32) -> i32 {
+        std::cmp::max(0, height - self.safe_fall_height) * self.fall_damage_per_height
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Initiative, TerrainRules};
+    use crate::game::character::Character;
+    use crate::game::generational_id_map::GenerationalIdMap;
+    use crate::game::id_map::{Id, IdMap};
+    use crate::game::rng::GameRng;
+    use crate::game::test_utils::game_definition;
+    use rand::Rng;
+    use serde_json::json;
+    use std::collections::{BTreeMap, VecDeque};
+
+    #[test]
+    fn test_alternate_teams() {
+        let g = game_definition();
+        let class = g.classes.get(Id::new(0)).unwrap();
+        // same class, hence same swiftness: only the tie breakers matter
+        let characters: GenerationalIdMap<_> = vec![(0, 0), (1, 0), (2, 0), (3, 1), (4, 1)]
+            .into_iter()
+            .map(|(id, team)| {
+                let character = Character::new(Id::new(0), Id::new(0), class, "c", Id::new(team));
+                (Id::new(id), character)
+            })
+            .collect::<IdMap<_>>()
+            .into();
+
+        let mut tied = BTreeMap::new();
+        for id in (0..5).map(Id::new) {
+            let team = characters.get(id).unwrap().team;
+            tied.entry(team).or_insert_with(VecDeque::new).push_back(id);
+        }
+
+        let mut order = Vec::new();
+        Initiative::alternate_teams(tied, Some(Id::new(0)), &characters, &mut order);
+        let order = order.into_iter().map(Id::raw).collect::<Vec<_>>();
+        assert_eq!(order, vec![3, 0, 4, 1, 2]);
+    }
+
+    #[test]
+    fn test_seeded_roll() {
+        let g = game_definition();
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let characters: GenerationalIdMap<_> = (0..6)
+            .map(|id| {
+                let character = Character::new(Id::new(0), Id::new(0), class, "c", Id::new(id % 2));
+                (Id::new(id), character)
+            })
+            .collect::<IdMap<_>>()
+            .into();
+
+        for initiative in &[Initiative::Swiftness, Initiative::Random] {
+            let first = initiative.roll(&g, &characters, &mut GameRng::from_seed(42));
+            let second = initiative.roll(&g, &characters, &mut GameRng::from_seed(42));
+            assert_eq!(first, second);
+            assert_eq!(first.len(), 6);
+        }
+    }
+
+    #[test]
+    fn test_swiftness_order() {
+        let mut g = game_definition();
+        let warrior = g.classes.get(Id::new(0)).unwrap().clone();
+        g.classes = vec![3, 5, 4]
+            .into_iter()
+            .enumerate()
+            .map(|(id, swiftness)| {
+                let mut class = warrior.clone();
+                class.swiftness = swiftness;
+                (Id::new(id), class)
+            })
+            .collect();
+        let characters: GenerationalIdMap<_> = (0..3)
+            .map(|id| {
+                let class = g.classes.get(Id::new(id)).unwrap();
+                let character = Character::new(Id::new(id), Id::new(0), class, "c", Id::new(0));
+                (Id::new(id), character)
+            })
+            .collect::<IdMap<_>>()
+            .into();
+
+        let mut rng = GameRng::from_seed(42);
+        let order = Initiative::Swiftness.roll(&g, &characters, &mut rng);
+        let order = order.into_iter().map(Id::raw).collect::<Vec<_>>();
+        assert_eq!(order, vec![1, 2, 0]);
+        // without ties, nothing was rolled
+        assert_eq!(rng.gen::<u64>(), GameRng::from_seed(42).gen::<u64>());
+    }
+
+    #[test]
+    fn test_terrain_modifiers() {
+        let mut g = game_definition();
+        let map = g.maps.get_mut(Id::new(0)).unwrap();
+        map.data[0] = serde_json::from_value(json!({ "height": 2, "attribute": "None" })).unwrap();
+        map.data[7] = serde_json::from_value(json!({ "height": 0, "attribute": "Wall" })).unwrap();
+        let skill = g.skills.get_mut(Id::new(0)).unwrap();
+        skill.range.max = 3;
+
+        let terrain = TerrainRules {
+            precision_per_height: 0.05,
+            damage_per_height: 0.1,
+            range_per_height: 1,
+            cover_damage_reduction: 0.25,
+        };
+        let (map, skill) = (
+            g.maps.get(Id::new(0)).unwrap(),
+            g.skills.get(Id::new(0)).unwrap(),
+        );
+        let neutral = TerrainRules::default();
+        let modifiers = neutral.modifiers(map, Id::new(0), Id::new(12), skill);
+        assert!(modifiers.damage == 1.0 && modifiers.precision == 0.0);
+        assert_eq!(modifiers.physical_damage, 1.0);
+        assert_eq!(neutral.range_bonus(map, Id::new(0), Id::new(2), skill), 0);
+
+        // the wall is next to the target, but not between both characters
+        let modifiers = terrain.modifiers(map, Id::new(0), Id::new(2), skill);
+        assert_eq!(modifiers.height_difference, 2);
+        assert!(modifiers.damage > 1.0 && modifiers.precision > 0.0);
+        assert!(!modifiers.cover);
+        assert_eq!(terrain.range_bonus(map, Id::new(0), Id::new(2), skill), 2);
+
+        let modifiers = terrain.modifiers(map, Id::new(0), Id::new(12), skill);
+        assert!(modifiers.cover && modifiers.physical_damage < 1.0);
+
+        // attacking upward
+        let modifiers = terrain.modifiers(map, Id::new(2), Id::new(0), skill);
+        assert!(modifiers.damage < 1.0 && modifiers.precision < 0.0);
+        assert_eq!(terrain.range_bonus(map, Id::new(2), Id::new(0), skill), 0);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::game::error::Error;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::GameState;
+use crate::game::rng::GameRng;
+use crate::game::session::Sessions;
+use crate::game::view::FogOfWar;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Bumped whenever the format of saved games changes in an incompatible way
+pub const SAVE_VERSION: u32 = 3;
+
+/// Everything needed to resume a running game: its state, but also its RNG (which is not part of
+/// the serialized GameState), the players' sessions and how the game was created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveGame {
+    pub version: u32,
+    pub definition_fingerprint: u64,
+    pub game_id: String,
+    pub team_size: usize,
+    pub fog: Option<FogOfWar>,
+    /// The actions played before the save cannot be undone, but the following ones can
+    pub allow_undo: bool,
+    pub sessions: Sessions,
+    pub rng: GameRng,
+    pub state: GameState,
+}
+
+impl SaveGame {
+    pub fn new<S: Into<String>>(
+        g: &GameDefinition,
+        game_id: S,
+        team_size: usize,
+        fog: Option<FogOfWar>,
+        allow_undo: bool,
+        sessions: Sessions,
+        state: &GameState,
+    ) -> SaveGame {
+        SaveGame {
+            version: SAVE_VERSION,
+            definition_fingerprint: g.fingerprint(),
+            game_id: game_id.into(),
+            team_size,
+            fog,
+            allow_undo,
+            sessions,
+            rng: state.rng.clone(),
+            state: state.clone(),
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R) -> io::Result<SaveGame> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(path: P) -> io::Result<SaveGame> {
+        SaveGame::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Fails if the game was saved by another version, or with another game definition
+    pub fn check_compatibility(&self, g: &GameDefinition) -> Result<(), Error> {
+        if self.version != SAVE_VERSION {
+            return Err(Error::UnsupportedSaveVersion);
+        }
+
+        if self.definition_fingerprint != g.fingerprint() {
+            return Err(Error::IncompatibleGameDefinition);
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(self, g: &GameDefinition) -> Result<(GameState, Sessions), Error> {
+        self.check_compatibility(g)?;
+
+        let mut state = self.state;
+        state.rng = self.rng;
+        Ok((state, self.sessions))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SaveGame;
+    use crate::game::error::Error;
+    use crate::game::id_map::Id;
+    use crate::game::test_utils::{game_definition, running_game};
+    use rand::Rng;
+
+    #[test]
+    fn test_save_restore() {
+        let g = game_definition();
+        let mut gs = running_game(&g);
+
+        let save = SaveGame::new(&g, "game", 1, None, false, Default::default(), &gs);
+        let mut bytes = Vec::new();
+        save.write(&mut bytes).unwrap();
+
+        let (mut restored, _) = SaveGame::read(bytes.as_slice())
+            .unwrap()
+            .restore(&g)
+            .unwrap();
+        assert_eq!(restored.turn_order, gs.turn_order);
+        assert_eq!(
+            restored.rng.gen::<u64>(),
+            gs.rng.gen::<u64>(),
+            "RNG state was not restored"
+        );
+    }
+
+    #[test]
+    fn test_restore_incompatible() {
+        let mut g = game_definition();
+        let gs = running_game(&g);
+        let save = SaveGame::new(&g, "game", 1, None, false, Default::default(), &gs);
+
+        // renaming things does not change the game
+        g.classes.get_mut(Id::new(0)).unwrap().name = "Knight".to_owned();
+        g.classes.get_mut(Id::new(0)).unwrap().description = "Hits hard".to_owned();
+        g.skills.get_mut(Id::new(0)).unwrap().name = "Strike".to_owned();
+        assert!(save.clone().restore(&g).is_ok());
+
+        g.classes.get_mut(Id::new(0)).unwrap().health += 1;
+        assert!(matches!(
+            save.clone().restore(&g),
+            Err(Error::IncompatibleGameDefinition)
+        ));
+
+        let mut save = save;
+        save.version += 1;
+        assert!(matches!(
+            save.restore(&g),
+            Err(Error::UnsupportedSaveVersion)
+        ));
+    }
+}
@@ -0,0 +1,581 @@
+use crate::game::character::Character;
+use crate::game::class::{Attribute, Class};
+use crate::game::damage::DamageType;
+use crate::game::effect::BuffKind;
+use crate::game::error::Error;
+use crate::game::map::GameMap;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Small language for effects which do not fit any of the built-in ones. A script is a list of
+/// actions separated by semicolons, whose arguments are arithmetic expressions over the caster,
+/// the target and the map, e.g.
+///
+/// `damage(magical, 0.5 * caster.intelligence + 0.1 * target.max_health); stun(if(caster.height > target.height, 2, 1))`
+///
+/// Actions: `damage(physical|magical|pure, amount)`, `heal(amount)`, `shield(amount, duration)`,
+/// `stun(duration)`, `root(duration)`, `silence(duration)`, `taunt(duration)`, `push(cells)` and
+/// `pull(cells)`.
+///
+/// Expressions: numbers, `+ - * /`, comparisons (1 if true, 0 otherwise), parentheses,
+/// `min(a, b)`, `max(a, b)`, `if(condition, a, b)`, `distance` (between caster and target) and
+/// `caster.<stat>` / `target.<stat>` where the stat is an attribute (e.g. `strength`), `health`,
+/// `max_health`, `missing_health`, `mana`, `shield` or `height` (of the character's cell).
+///
+/// Scripts cannot do anything else: they only describe actions, which the game state applies.
+/// They are parsed once, when loaded: invalid scripts fail to deserialize.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Script {
+    source: String,
+    statements: Vec<Statement>,
+}
+
+impl TryFrom<String> for Script {
+    type Error = Error;
+
+    fn try_from(source: String) -> Result<Self, Error> {
+        let statements = parse(&source)?;
+        Ok(Script { source, statements })
+    }
+}
+
+impl From<Script> for String {
+    fn from(script: Script) -> Self {
+        script.source
+    }
+}
+
+/// What a script asks the game state to do to the target
+#[derive(Debug, Clone)]
+pub enum ScriptAction {
+    Damage(DamageType, i32),
+    Heal(i32),
+    /// The buff is named after the action which created it, e.g. "stun"
+    Buff {
+        name: String,
+        kind: BuffKind,
+        duration: i32,
+    },
+    Push(u32),
+    Pull(u32),
+}
+
+/// Everything a script can read
+pub struct ScriptContext<'a> {
+    pub map: &'a GameMap,
+    pub caster: &'a Character,
+    pub c_caster: &'a Class,
+    pub target: &'a Character,
+    pub c_target: &'a Class,
+}
+
+impl Script {
+    pub fn new<S: Into<String>>(source: S) -> Result<Self, Error> {
+        Script::try_from(source.into())
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Every action is computed from the state before the script runs
+    pub fn run(&self, context: &ScriptContext) -> Vec<ScriptAction> {
+        self.statements
+            .iter()
+            .map(|statement| statement.run(context))
+            .collect()
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Statement>, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?.into_iter().peekable(),
+    };
+
+    let mut statements = Vec::new();
+    while parser.tokens.peek().is_some() {
+        statements.push(parser.statement()?);
+        match parser.tokens.next() {
+            Some(Token::Semicolon) | None => (),
+            Some(token) => return Err(unexpected(&token)),
+        }
+    }
+    Ok(statements)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Identifier(String),
+    Operator(BinaryOp),
+    Minus,
+    Dot,
+    Comma,
+    Semicolon,
+    OpenParen,
+    CloseParen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::InvalidScript(message.into())
+}
+
+fn unexpected(token: &Token) -> Error {
+    invalid(format!("Unexpected {:?}", token))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '0'..='9' => {
+                let mut number = c.to_string();
+                take_while(&mut chars, &mut number, |c| c.is_ascii_digit() || c == '.');
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| invalid(format!("Invalid number {}", number)))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut identifier = c.to_string();
+                take_while(&mut chars, &mut identifier, |c| {
+                    c.is_alphanumeric() || c == '_'
+                });
+                Token::Identifier(identifier)
+            }
+            '+' => Token::Operator(BinaryOp::Add),
+            '-' => Token::Minus,
+            '*' => Token::Operator(BinaryOp::Mul),
+            '/' => Token::Operator(BinaryOp::Div),
+            '<' | '>' | '=' | '!' => {
+                let equal = chars.peek() == Some(&'=');
+                if equal {
+                    chars.next();
+                }
+                Token::Operator(match (c, equal) {
+                    ('<', false) => BinaryOp::Less,
+                    ('<', true) => BinaryOp::LessEqual,
+                    ('>', false) => BinaryOp::Greater,
+                    ('>', true) => BinaryOp::GreaterEqual,
+                    ('=', true) => BinaryOp::Equal,
+                    ('!', true) => BinaryOp::NotEqual,
+                    _ => return Err(invalid(format!("Unexpected character {}", c))),
+                })
+            }
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            c => return Err(invalid(format!("Unexpected character {}", c))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn take_while<F: Fn(char) -> bool>(chars: &mut Peekable<Chars>, buffer: &mut String, f: F) {
+    while let Some(c) = chars.peek() {
+        if !f(*c) {
+            break;
+        }
+        buffer.push(*c);
+        chars.next();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Subject {
+    Caster,
+    Target,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stat {
+    Attribute(Attribute),
+    Health,
+    MaxHealth,
+    MissingHealth,
+    Mana,
+    Shield,
+    Height,
+}
+
+impl Stat {
+    fn parse(name: &str) -> Option<Stat> {
+        Some(match name {
+            "health" => Stat::Health,
+            "max_health" => Stat::MaxHealth,
+            "missing_health" => Stat::MissingHealth,
+            "mana" => Stat::Mana,
+            "shield" => Stat::Shield,
+            "height" => Stat::Height,
+            "swiftness" => Stat::Attribute(Attribute::Swiftness),
+            "strength" => Stat::Attribute(Attribute::Strength),
+            "dexterity" => Stat::Attribute(Attribute::Dexterity),
+            "armor" => Stat::Attribute(Attribute::Armor),
+            "intelligence" => Stat::Attribute(Attribute::Intelligence),
+            "concentration" => Stat::Attribute(Attribute::Concentration),
+            "willpower" => Stat::Attribute(Attribute::Willpower),
+            "vision" => Stat::Attribute(Attribute::Vision),
+            "action_points" => Stat::Attribute(Attribute::ActionPoints),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Min,
+    Max,
+    If,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f32),
+    Stat(Subject, Stat),
+    Distance,
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Box<[Expr; 3]>),
+}
+
+impl Expr {
+    fn eval(&self, context: &ScriptContext) -> f32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Stat(subject, stat) => {
+                let (character, class) = match subject {
+                    Subject::Caster => (context.caster, context.c_caster),
+                    Subject::Target => (context.target, context.c_target),
+                };
+                let value = match stat {
+                    Stat::Attribute(attribute) => character.effective_attribute(class, *attribute),
+                    Stat::Health => character.current_health,
+                    Stat::MaxHealth => character.effective_health(class),
+                    Stat::MissingHealth => {
+                        character.effective_health(class) - character.current_health
+                    }
+                    Stat::Mana => character.current_mana,
+                    Stat::Shield => character.shield(),
+                    Stat::Height => context.map[character.position].height(),
+                };
+                value as f32
+            }
+            Expr::Distance => context
+                .map
+                .distance(context.caster.position, context.target.position)
+                as f32,
+            Expr::Neg(expr) => -expr.eval(context),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(context), right.eval(context));
+                let test = |condition: bool| if condition { 1.0 } else { 0.0 };
+                match op {
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Sub => left - right,
+                    BinaryOp::Mul => left * right,
+                    BinaryOp::Div => left / right,
+                    BinaryOp::Less => test(left < right),
+                    BinaryOp::LessEqual => test(left <= right),
+                    BinaryOp::Greater => test(left > right),
+                    BinaryOp::GreaterEqual => test(left >= right),
+                    BinaryOp::Equal => test((left - right).abs() < f32::EPSILON),
+                    BinaryOp::NotEqual => test((left - right).abs() >= f32::EPSILON),
+                }
+            }
+            Expr::Call(function, args) => {
+                let [a, b, c] = &**args;
+                match function {
+                    Function::Min => f32::min(a.eval(context), b.eval(context)),
+                    Function::Max => f32::max(a.eval(context), b.eval(context)),
+                    Function::If if a.eval(context) != 0.0 => b.eval(context),
+                    Function::If => c.eval(context),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Damage(DamageType, Expr),
+    Heal(Expr),
+    Shield(Expr, Expr),
+    /// Buffs without any value, e.g. stun
+    Status(&'static str, BuffKind, Expr),
+    Push(Expr),
+    Pull(Expr),
+}
+
+impl Statement {
+    fn run(&self, context: &ScriptContext) -> ScriptAction {
+        // casts saturate, e.g. negative values give 0 cells
+        let int = |expr: &Expr| expr.eval(context) as i32;
+        let cells = |expr: &Expr| expr.eval(context) as u32;
+        match self {
+            Statement::Damage(damage_type, amount) => {
+                ScriptAction::Damage(*damage_type, int(amount))
+            }
+            Statement::Heal(amount) => ScriptAction::Heal(int(amount)),
+            Statement::Shield(amount, duration) => ScriptAction::Buff {
+                name: "shield".to_owned(),
+                kind: BuffKind::Shield(int(amount)),
+                duration: int(duration),
+            },
+            Statement::Status(name, kind, duration) => ScriptAction::Buff {
+                name: (*name).to_owned(),
+                kind: kind.clone(),
+                duration: int(duration),
+            },
+            Statement::Push(distance) => ScriptAction::Push(cells(distance)),
+            Statement::Pull(distance) => ScriptAction::Pull(cells(distance)),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn expect(&mut self, expected: Token) -> Result<(), Error> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(unexpected(&token)),
+            None => Err(invalid(format!("Expected {:?}", expected))),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, Error> {
+        match self.tokens.next() {
+            Some(Token::Identifier(identifier)) => Ok(identifier),
+            Some(token) => Err(unexpected(&token)),
+            None => Err(invalid("Expected an identifier")),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, Error> {
+        let action = self.identifier()?;
+        self.expect(Token::OpenParen)?;
+
+        let statement = match action.as_str() {
+            "damage" => {
+                let damage_type = match self.identifier()?.as_str() {
+                    "physical" => DamageType::Physical,
+                    "magical" => DamageType::Magical,
+                    "pure" => DamageType::Pure,
+                    other => return Err(invalid(format!("Unknown damage type {}", other))),
+                };
+                self.expect(Token::Comma)?;
+                Statement::Damage(damage_type, self.expr()?)
+            }
+            "heal" => Statement::Heal(self.expr()?),
+            "shield" => {
+                let amount = self.expr()?;
+                self.expect(Token::Comma)?;
+                Statement::Shield(amount, self.expr()?)
+            }
+            "stun" => Statement::Status("stun", BuffKind::Stun, self.expr()?),
+            "root" => Statement::Status("root", BuffKind::Root, self.expr()?),
+            "silence" => Statement::Status("silence", BuffKind::Silence, self.expr()?),
+            "taunt" => Statement::Status("taunt", BuffKind::Taunt, self.expr()?),
+            "push" => Statement::Push(self.expr()?),
+            "pull" => Statement::Pull(self.expr()?),
+            other => return Err(invalid(format!("Unknown action {}", other))),
+        };
+
+        self.expect(Token::CloseParen)?;
+        Ok(statement)
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let left = self.additive()?;
+        match self.tokens.peek() {
+            Some(Token::Operator(op))
+                if !matches!(op, BinaryOp::Add | BinaryOp::Mul | BinaryOp::Div) =>
+            {
+                let op = *op;
+                self.tokens.next();
+                Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, Error> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.tokens.peek() {
+                Some(Token::Operator(BinaryOp::Add)) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.tokens.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.tokens.peek() {
+                Some(Token::Operator(op @ BinaryOp::Mul))
+                | Some(Token::Operator(op @ BinaryOp::Div)) => *op,
+                _ => return Ok(left),
+            };
+            self.tokens.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.tokens.peek() == Some(&Token::Minus) {
+            self.tokens.next();
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.tokens.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::OpenParen) => {
+                let expr = self.expr()?;
+                self.expect(Token::CloseParen)?;
+                Ok(expr)
+            }
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "distance" => Ok(Expr::Distance),
+                "caster" | "target" => {
+                    let subject = if name == "caster" {
+                        Subject::Caster
+                    } else {
+                        Subject::Target
+                    };
+                    self.expect(Token::Dot)?;
+                    let stat = self.identifier()?;
+                    let stat = Stat::parse(&stat)
+                        .ok_or_else(|| invalid(format!("Unknown stat {}", stat)))?;
+                    Ok(Expr::Stat(subject, stat))
+                }
+                "min" | "max" | "if" => {
+                    let (function, arity) = match name.as_str() {
+                        "min" => (Function::Min, 2),
+                        "max" => (Function::Max, 2),
+                        _ => (Function::If, 3),
+                    };
+                    self.expect(Token::OpenParen)?;
+                    let mut args = [Expr::Number(0.0), Expr::Number(0.0), Expr::Number(0.0)];
+                    for (i, arg) in args.iter_mut().take(arity).enumerate() {
+                        if i > 0 {
+                            self.expect(Token::Comma)?;
+                        }
+                        *arg = self.expr()?;
+                    }
+                    self.expect(Token::CloseParen)?;
+                    Ok(Expr::Call(function, Box::new(args)))
+                }
+                other => Err(invalid(format!("Unknown variable {}", other))),
+            },
+            Some(token) => Err(unexpected(&token)),
+            None => Err(invalid("Unexpected end of script")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Script, ScriptAction, ScriptContext};
+    use crate::game::character::Character;
+    use crate::game::damage::DamageType;
+    use crate::game::effect::BuffKind;
+    use crate::game::error::Error;
+    use crate::game::id_map::Id;
+    use crate::game::test_utils::game_definition;
+    use serde_json::json;
+
+    #[test]
+    fn test_run() {
+        let mut g = game_definition();
+        g.classes.get_mut(Id::new(0)).unwrap().intelligence = 10;
+        let class = g.classes.get(Id::new(0)).unwrap();
+        let caster = Character::new(Id::new(0), Id::new(0), class, "caster", Id::new(0));
+        let mut target = Character::new(Id::new(0), Id::new(2), class, "target", Id::new(1));
+        target.current_health = 15;
+
+        let context = ScriptContext {
+            map: g.maps.get(Id::new(0)).unwrap(),
+            caster: &caster,
+            c_caster: class,
+            target: &target,
+            c_target: class,
+        };
+        let script = Script::new(
+            "damage(magical, 0.5 * caster.intelligence + 0.1 * target.max_health); \
+             heal(-(2 - 3) * target.missing_health); \
+             stun(if(distance >= 2, 2, 1)); push(max(distance, 1) / 2);",
+        )
+        .unwrap();
+        let actions = script.run(&context);
+        assert!(matches!(
+            actions.as_slice(),
+            [
+                ScriptAction::Damage(DamageType::Magical, 7),
+                ScriptAction::Heal(5),
+                ScriptAction::Buff {
+                    kind: BuffKind::Stun,
+                    duration: 2,
+                    ..
+                },
+                ScriptAction::Push(1),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for source in &[
+            "explode(3)",
+            "damage(fire, 3)",
+            "heal(caster.luck)",
+            "heal(3",
+            "heal(3) heal(4)",
+            "heal(3 $ 4)",
+            "heal(min(1))",
+        ] {
+            assert!(
+                matches!(Script::new(*source), Err(Error::InvalidScript(_))),
+                "{} should be invalid",
+                source
+            );
+            assert!(serde_json::from_value::<Script>(json!(source)).is_err());
+        }
+        assert!(Script::new("").is_ok());
+
+        let script: Script = serde_json::from_value(json!("heal(3)")).unwrap();
+        assert_eq!(serde_json::to_value(&script).unwrap(), json!("heal(3)"));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::game::character::Character;
+use crate::game::error::Error;
+use crate::game::game_state::GameState;
+use crate::game::id_map::Id;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Secret handed to a player when they create their first character. It must be sent along with
+/// every request regarding their characters, and is what allows them to reclaim those after a
+/// disconnection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlayerToken(String);
+
+impl PlayerToken {
+    pub fn generate() -> PlayerToken {
+        PlayerToken(
+            thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(TOKEN_LENGTH)
+                .collect(),
+        )
+    }
+}
+
+/// Keeps track of which player controls which character
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Sessions {
+    owners: BTreeMap<Id<Character>, PlayerToken>,
+    /// Players stay known even once they have left all their characters
+    #[serde(default)]
+    tokens: BTreeSet<PlayerToken>,
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Sessions {
+            owners: BTreeMap::new(),
+            tokens: BTreeSet::new(),
+        }
+    }
+
+    /// A player creating their first character does not have a token yet, and is issued a new one
+    pub fn register(
+        &mut self,
+        token: Option<&PlayerToken>,
+        character: Id<Character>,
+    ) -> Result<PlayerToken, Error> {
+        let token = match token {
+            Some(token) if self.is_known(token) => token.clone(),
+            Some(_) => return Err(Error::InvalidToken),
+            None => PlayerToken::generate(),
+        };
+        self.owners.insert(character, token.clone());
+        self.tokens.insert(token.clone());
+        Ok(token)
+    }
+
+    pub fn unregister(&mut self, character: Id<Character>) -> Option<PlayerToken> {
+        self.owners.remove(&character)
+    }
+
+    pub fn is_known(&self, token: &PlayerToken) -> bool {
+        self.tokens.contains(token)
+    }
+
+    pub fn owner(&self, character: Id<Character>) -> Option<&PlayerToken> {
+        self.owners.get(&character)
+    }
+
+    /// Used when a player reconnects to find back their characters
+    pub fn characters_of(&self, token: &PlayerToken) -> Result<Vec<Id<Character>>, Error> {
+        if !self.is_known(token) {
+            return Err(Error::InvalidToken);
+        }
+
+        Ok(self
+            .owners
+            .iter()
+            .filter(|(_, owner)| *owner == token)
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    pub fn check_owner(&self, token: &PlayerToken, character: Id<Character>) -> Result<(), Error> {
+        match self.owners.get(&character) {
+            Some(owner) if owner == token => Ok(()),
+            _ if !self.is_known(token) => Err(Error::InvalidToken),
+            Some(_) => Err(Error::NotCharacterOwner),
+            None => Err(Error::InvalidCharacterId),
+        }
+    }
+
+    /// Only the owner of the character whose turn it is may send an action. Summons have no owner
+    /// of their own, and are played by the owner of their summoner.
+    pub fn check_action(&self, token: &PlayerToken, gs: &GameState) -> Result<(), Error> {
+        let mut character = gs.player_to_play();
+        while let Some(summoner) = gs.characters.get(character).and_then(|c| c.summoner) {
+            character = summoner;
+        }
+        self.check_owner(token, character)
+    }
+}
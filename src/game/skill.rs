@@ -1,14 +1,48 @@
 use crate::game::effect::{Effect, Range};
 use crate::game::id_map::Id;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Skill {
     pub name: String,
 
     pub cost: i32,
+    /// Only used with the action points turn model
+    #[serde(default = "Skill::default_action_points")]
+    pub action_points: i32,
     pub range: Range,
     pub precision: Option<f32>,
-    pub effects: HashSet<Id<Effect>>,
+    /// Magical skills cannot be used while silenced
+    #[serde(default)]
+    pub magical: bool,
+    pub effects: BTreeSet<Id<Effect>>,
+
+    /// Number of rounds before the skill can be used again (0 means it can be reused right away)
+    #[serde(default)]
+    pub cooldown: u32,
+    /// Only matters with the action points turn model, where several skills can be used per turn
+    #[serde(default)]
+    pub uses_per_turn: Option<u32>,
+    #[serde(default)]
+    pub uses_per_game: Option<u32>,
+    /// Uses stored by the character, on top of the other limits. None means the skill does not use
+    /// charges.
+    #[serde(default)]
+    pub charges: Option<u32>,
+    /// Number of rounds it takes to recover a used charge, one charge at a time (0 means they
+    /// never come back)
+    #[serde(default)]
+    pub recharge: u32,
+}
+
+impl Skill {
+    /// Anything which can reach further than the cells next to the caster
+    pub fn is_ranged(&self) -> bool {
+        self.range.max > 1
+    }
+
+    fn default_action_points() -> i32 {
+        1
+    }
 }
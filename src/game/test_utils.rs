@@ -0,0 +1,106 @@
+use crate::game::character::Character;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::GameState;
+use crate::game::id_map::Id;
+use crate::game::lobby::Lobby;
+use crate::game::map::Cell;
+use serde_json::json;
+
+/// 5x5 flat map with two teams of two starting cells (left and right columns), a single class and
+/// a melee attack
+pub fn game_definition() -> GameDefinition {
+    let cell = json!({ "height": 0, "attribute": "None" });
+    serde_json::from_value(json!({
+        "classes": {
+            "0": {
+                "name": "Warrior",
+                "description": "Hits things",
+                "health": 20,
+                "mana": 0,
+                "swiftness": 3,
+                "strength": 5,
+                "dexterity": 5,
+                "armor": 1,
+                "intelligence": 0,
+                "concentration": 0,
+                "willpower": 0
+            }
+        },
+        "skills": {
+            "0": {
+                "name": "Slash",
+                "cost": 0,
+                "range": { "min": 1, "max": 1, "kind": "Star", "target": "Enemy", "needs_los": true },
+                "precision": null,
+                "effects": [0]
+            }
+        },
+        "effects": {
+            "0": {
+                "id": 0,
+                "kind": { "DirectDamage": { "range": null, "damage": { "flat_physical": 6 } } }
+            }
+        },
+        "maps": {
+            "0": {
+                "name": "Arena",
+                "data": vec![cell; 25],
+                "width": 5,
+                "height": 5,
+                "teams": [["Left", [0, 20]], ["Right", [4, 24]]]
+            }
+        },
+        "class_to_skills": { "0": [0] },
+        "skill_to_classes": { "0": [0] }
+    }))
+    .expect("Invalid test game definition")
+}
+
+/// One character on each side of the map, in its top corner
+pub fn running_game(g: &GameDefinition) -> GameState {
+    let mut lobby = Lobby::new(g, Id::new(0), 1).unwrap();
+    let (first, first_token) = lobby
+        .join(None, "first", Id::new(0), Id::new(0), Id::new(0))
+        .unwrap();
+    let (second, second_token) = lobby
+        .join(None, "second", Id::new(0), Id::new(1), Id::new(4))
+        .unwrap();
+    lobby.set_ready(&first_token, first, true).unwrap();
+    lobby.set_ready(&second_token, second, true).unwrap();
+    lobby.try_start().unwrap().0
+}
+
+/// Both characters of a running game, seen from the one playing first
+pub struct Duel {
+    pub attacker: Id<Character>,
+    pub enemy: Id<Character>,
+    pub start: Id<Cell>,
+    pub enemy_cell: Id<Cell>,
+    /// Direction of the enemy along the top row (1 or -1)
+    step: i32,
+}
+
+impl Duel {
+    pub fn new(gs: &GameState) -> Self {
+        let attacker = gs.player_to_play();
+        let (enemy, enemy_character) = gs
+            .characters
+            .iter()
+            .find(|(id, _)| *id != attacker)
+            .unwrap();
+        let start = gs.characters.get(attacker).unwrap().position;
+        Duel {
+            attacker,
+            enemy,
+            start,
+            enemy_cell: enemy_character.position,
+            step: if start.raw() == 0 { 1 } else { -1 },
+        }
+    }
+
+    /// Cell of the top row, the given number of cells away from the attacker's starting cell
+    /// toward the enemy (3 being next to them)
+    pub fn toward_enemy(&self, cells: i32) -> Id<Cell> {
+        Id::new((self.start.raw() as i32 + cells * self.step) as usize)
+    }
+}
@@ -0,0 +1,157 @@
+use crate::game::error::Error;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::{Action, GameState};
+
+/// Undo/redo stack for local (hotseat) and tutorial games, made of snapshots of the game.
+/// Only actions which did not reveal any random information (i.e. did not draw from the game's
+/// RNG, like a hit roll or a new round's initiative) can be undone: any such action clears the
+/// history.
+#[derive(Debug, Clone, Default)]
+pub struct UndoHistory {
+    undo: Vec<GameState>,
+    redo: Vec<GameState>,
+}
+
+impl UndoHistory {
+    pub fn new() -> Self {
+        UndoHistory::default()
+    }
+
+    pub fn next_action(
+        &mut self,
+        gs: &mut GameState,
+        g: &GameDefinition,
+        action: Action,
+    ) -> Result<bool, Error> {
+        let before = gs.clone();
+        let new_turn = gs.next_action(g, action)?;
+
+        self.redo.clear();
+        if gs.rng.draws() == before.rng.draws() {
+            self.undo.push(before);
+        } else {
+            self.undo.clear();
+        }
+        Ok(new_turn)
+    }
+
+    /// State the game would go back to when undoing
+    pub fn peek_undo(&self) -> Option<&GameState> {
+        self.undo.last()
+    }
+
+    pub fn peek_redo(&self) -> Option<&GameState> {
+        self.redo.last()
+    }
+
+    pub fn undo(&mut self, gs: &mut GameState) -> Result<(), Error> {
+        let previous = self.undo.pop().ok_or(Error::NothingToUndo)?;
+        self.redo.push(std::mem::replace(gs, previous));
+        Ok(())
+    }
+
+    pub fn redo(&mut self, gs: &mut GameState) -> Result<(), Error> {
+        let next = self.redo.pop().ok_or(Error::NothingToRedo)?;
+        self.undo.push(std::mem::replace(gs, next));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UndoHistory;
+    use crate::game::error::Error;
+    use crate::game::game_state::{Action, GameState, TurnState};
+    use crate::game::id_map::Id;
+    use crate::game::rules::TurnModel;
+    use crate::game::test_utils::{game_definition, running_game, Duel};
+    use serde_json::Value;
+
+    fn snapshot(gs: &GameState) -> Value {
+        serde_json::to_value(gs).unwrap()
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let g = game_definition();
+        let mut gs = running_game(&g);
+        let mut history = UndoHistory::new();
+
+        let initial = snapshot(&gs);
+        history.next_action(&mut gs, &g, Action::Pass).unwrap();
+        let after_pass = snapshot(&gs);
+
+        history.undo(&mut gs).unwrap();
+        assert_eq!(snapshot(&gs), initial);
+        assert!(matches!(history.undo(&mut gs), Err(Error::NothingToUndo)));
+
+        history.redo(&mut gs).unwrap();
+        assert_eq!(snapshot(&gs), after_pass);
+        assert!(matches!(history.redo(&mut gs), Err(Error::NothingToRedo)));
+    }
+
+    #[test]
+    fn test_random_actions_cannot_be_undone() {
+        let g = game_definition();
+        let mut gs = running_game(&g);
+        let mut history = UndoHistory::new();
+
+        history.next_action(&mut gs, &g, Action::Pass).unwrap();
+        assert!(history.peek_undo().is_some());
+
+        // the second pass ends the round, which rolls the initiative of the one after
+        let draws = gs.rng.draws();
+        history.next_action(&mut gs, &g, Action::Pass).unwrap();
+        assert!(gs.rng.draws() > draws);
+        assert!(history.peek_undo().is_none());
+    }
+
+    #[test]
+    fn test_undo_move() {
+        let mut g = game_definition();
+        g.rules.turn_model = TurnModel::ActionPoints;
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let mut history = UndoHistory::new();
+
+        let turn_state = gs.turn_state;
+        let cell = duel.toward_enemy(2);
+        history
+            .next_action(&mut gs, &g, Action::Movement(cell))
+            .unwrap();
+        assert_eq!(gs.characters.get(duel.attacker).unwrap().position, cell);
+        assert!(matches!(
+            gs.turn_state,
+            TurnState::Points { movement: 1, .. }
+        ));
+
+        history.undo(&mut gs).unwrap();
+        let character = gs.characters.get(duel.attacker).unwrap();
+        assert_eq!(character.position, duel.start);
+        assert_eq!(gs.turn_state, turn_state);
+    }
+
+    #[test]
+    fn test_rolled_skills_cannot_be_undone() {
+        let mut g = game_definition();
+        g.rules.turn_model = TurnModel::ActionPoints;
+        g.skills.get_mut(Id::new(0)).unwrap().precision = Some(0.5);
+        let mut gs = running_game(&g);
+        let duel = Duel::new(&gs);
+        let mut history = UndoHistory::new();
+
+        history
+            .next_action(&mut gs, &g, Action::Movement(duel.toward_enemy(3)))
+            .unwrap();
+        assert!(history.peek_undo().is_some());
+
+        // the hit roll, then the crit roll if it hit
+        let draws = gs.rng.draws();
+        history
+            .next_action(&mut gs, &g, Action::Skill(Id::new(0), duel.enemy_cell))
+            .unwrap();
+        assert!(gs.rng.draws() > draws);
+        assert!(history.peek_undo().is_none());
+        assert!(matches!(history.undo(&mut gs), Err(Error::NothingToUndo)));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::game::character::{Character, SkillUsage};
+use crate::game::class::Class;
+use crate::game::effect::Buff;
+use crate::game::event::Event;
+use crate::game::game_definition::GameDefinition;
+use crate::game::game_state::{GameState, TurnState};
+use crate::game::id_map::Id;
+use crate::game::map::{Cell, GameMap, Team};
+use crate::game::skill::Skill;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FogOfWar {
+    /// Enemy health is rounded to the closest multiple of this value, None meaning the exact
+    /// health is shown
+    #[serde(default)]
+    pub health_rounding: Option<i32>,
+}
+
+/// Buff as seen by a team, whose caster is only known if they are in the view
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuffView {
+    pub buff: Buff,
+    pub caster: Option<Id<Character>>,
+    pub remaining: i32,
+}
+
+/// Character as seen by a team: the mana, skill usage and reactions of enemies are unknown
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterView {
+    pub name: String,
+    pub class: Id<Class>,
+    pub current_health: i32,
+    pub current_mana: Option<i32>,
+    pub position: Id<Cell>,
+    pub buffs: Vec<BuffView>,
+    pub team: Id<Team>,
+    pub skill_usage: Option<BTreeMap<Id<Skill>, SkillUsage>>,
+    pub reactions_left: Option<u32>,
+    pub summoner: Option<Id<Character>>,
+    pub lifespan: Option<u32>,
+}
+
+/// Entry of a turn order as seen by a team
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnSlot {
+    Character(Id<Character>),
+    /// Character outside the vision of the team, whose turn is known to happen but not whose it is
+    Hidden,
+}
+
+/// What a single team is allowed to know about the game: enemies outside the vision of every
+/// ally are removed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameStateView {
+    pub team: Id<Team>,
+    pub map: Id<GameMap>,
+    pub characters: BTreeMap<Id<Character>, CharacterView>,
+    pub turn_order: Vec<TurnSlot>,
+    pub next_turn_order: Vec<TurnSlot>,
+    /// Only known while an ally is playing
+    pub turn_state: Option<TurnState>,
+}
+
+impl GameStateView {
+    pub fn new(
+        gs: &GameState,
+        g: &GameDefinition,
+        team: Id<Team>,
+        fog: &FogOfWar,
+    ) -> GameStateView {
+        let map = g.maps.get(gs.map).expect("Invalid game map id");
+        let allies = gs
+            .characters
+            .iter()
+            .filter(|(_, character)| character.team == team && character.is_alive())
+            .map(|(_, character)| character)
+            .collect::<Vec<_>>();
+
+        let is_visible = |character: &Character| {
+            character.team == team
+                || allies.iter().any(|ally| {
+                    let class = g.classes.get(ally.class).expect("Invalid class id");
+                    map.distance(ally.position, character.position)
+                        <= ally.effective_vision(class).max(0) as u32
+                        && map.has_line_of_sight(ally.position, character.position)
+                })
+        };
+
+        let visible = gs
+            .characters
+            .iter()
+            .filter(|(_, character)| is_visible(character))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        let known = |id: Id<Character>| Some(id).filter(|id| visible.contains(id));
+
+        let characters = visible
+            .iter()
+            .map(|id| {
+                let character = gs.characters.get(*id).expect("Invalid character id");
+                let ally = character.team == team;
+                let current_health = match fog.health_rounding {
+                    Some(step) if !ally => {
+                        GameStateView::round_health(character.current_health, step)
+                    }
+                    _ => character.current_health,
+                };
+                let buffs = character
+                    .buffs
+                    .iter()
+                    .map(|instance| BuffView {
+                        buff: instance.buff.clone(),
+                        caster: known(instance.caster),
+                        remaining: instance.remaining,
+                    })
+                    .collect();
+
+                let view = CharacterView {
+                    name: character.name.clone(),
+                    class: character.class,
+                    current_health,
+                    current_mana: Some(character.current_mana).filter(|_| ally),
+                    position: character.position,
+                    buffs,
+                    team: character.team,
+                    skill_usage: Some(character.skill_usage.clone()).filter(|_| ally),
+                    reactions_left: Some(character.reactions_left).filter(|_| ally),
+                    summoner: character.summoner.and_then(known),
+                    lifespan: character.lifespan,
+                };
+                (*id, view)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let hide = |turn_order: &[Id<Character>]| {
+            turn_order
+                .iter()
+                .map(|id| known(*id).map_or(TurnSlot::Hidden, TurnSlot::Character))
+                .collect()
+        };
+        let turn_order = hide(&gs.turn_order);
+        let next_turn_order = hide(&gs.next_turn_order);
+
+        let playing = gs.turn_order.last().and_then(|id| gs.characters.get(*id));
+        let turn_state = match playing {
+            Some(character) if character.team == team => Some(gs.turn_state),
+            _ => None,
+        };
+
+        GameStateView {
+            team,
+            map: gs.map,
+            characters,
+            turn_order,
+            next_turn_order,
+            turn_state,
+        }
+    }
+
+    /// Events are kept if every character they mention is in the view. A hidden character
+    /// damaging a visible one only has their id removed from the event.
+    /// Removed summons are not in the view anymore, hence their expiry is only seen by their
+    /// absence.
+    pub fn visible_events(&self, events: &[Event]) -> Vec<Event> {
+        let visible = |id: Id<Character>| self.characters.contains_key(&id);
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Damaged {
+                    character,
+                    source,
+                    damage,
+                } if visible(*character) => Some(Event::Damaged {
+                    character: *character,
+                    source: source.filter(|source| visible(*source)),
+                    damage: *damage,
+                }),
+                event if event.characters().into_iter().all(visible) => Some(event.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // a living character never appears dead (and conversely)
+    fn round_health(health: i32, step: i32) -> i32 {
+        if step <= 1 || health <= 0 {
+            return health;
+        }
+        std::cmp::max(step, (health + step / 2) / step * step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FogOfWar, GameStateView, TurnSlot};
+    use crate::game::character::{BuffInstance, Character};
+    use crate::game::damage::DamageBreakdown;
+    use crate::game::effect::{Buff, BuffKind};
+    use crate::game::event::Event;
+    use crate::game::id_map::Id;
+    use crate::game::test_utils::{game_definition, running_game};
+    use serde_json::json;
+
+    #[test]
+    fn test_hidden_characters() {
+        let mut g = game_definition();
+        g.classes.get_mut(Id::new(0)).unwrap().vision = 2;
+        let mut gs = running_game(&g);
+        let (ally, _) = gs
+            .characters
+            .iter()
+            .find(|(_, character)| character.team == Id::new(0))
+            .unwrap();
+        let (enemy, _) = gs
+            .characters
+            .iter()
+            .find(|(_, character)| character.team == Id::new(1))
+            .unwrap();
+        let poison = Buff {
+            name: "Poison".to_owned(),
+            range: None,
+            success_rate: None,
+            duration: 3,
+            kind: BuffKind::DoT(serde_json::from_value(json!({ "flat_pure": 2 })).unwrap()),
+        };
+        let character = gs.characters.get_mut(ally).unwrap();
+        character.buffs.push(BuffInstance::new(poison, enemy));
+
+        // both characters are 4 cells apart
+        let fog = FogOfWar::default();
+        let view = GameStateView::new(&gs, &g, Id::new(0), &fog);
+        assert_eq!(view.characters.keys().collect::<Vec<_>>(), vec![&ally]);
+        let slots = |turn_order: &[Id<Character>]| {
+            turn_order
+                .iter()
+                .map(|id| {
+                    if *id == ally {
+                        TurnSlot::Character(ally)
+                    } else {
+                        TurnSlot::Hidden
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(view.turn_order, slots(&gs.turn_order));
+        assert_eq!(view.next_turn_order, slots(&gs.next_turn_order));
+        let expected = if gs.player_to_play() == ally {
+            Some(gs.turn_state)
+        } else {
+            None
+        };
+        assert_eq!(view.turn_state, expected);
+        let character = &view.characters[&ally];
+        assert_eq!(character.current_mana, Some(0));
+        assert!(character.skill_usage.is_some());
+        assert_eq!(character.buffs[0].caster, None);
+
+        let damage = DamageBreakdown::default();
+        let events = [
+            Event::Damaged {
+                character: ally,
+                source: Some(enemy),
+                damage,
+            },
+            Event::Moved {
+                character: enemy,
+                from: Id::new(4),
+                to: Id::new(3),
+            },
+            Event::Missed(ally),
+        ];
+        let expected = vec![
+            Event::Damaged {
+                character: ally,
+                source: None,
+                damage,
+            },
+            Event::Missed(ally),
+        ];
+        assert_eq!(view.visible_events(&events), expected);
+
+        g.classes.get_mut(Id::new(0)).unwrap().vision = 4;
+        let fog = FogOfWar {
+            health_rounding: Some(8),
+        };
+        let view = GameStateView::new(&gs, &g, Id::new(0), &fog);
+        assert_eq!(view.characters.len(), 2);
+        let expected = gs
+            .turn_order
+            .iter()
+            .map(|id| TurnSlot::Character(*id))
+            .collect::<Vec<_>>();
+        assert_eq!(view.turn_order, expected);
+        assert_eq!(view.characters[&ally].buffs[0].caster, Some(enemy));
+        for (id, character) in view.characters.iter() {
+            let expected = if *id == ally { 20 } else { 24 };
+            assert_eq!(character.current_health, expected);
+        }
+
+        // enemies only show what can be seen on the board
+        let enemy = &view.characters[&enemy];
+        assert_eq!(enemy.current_mana, None);
+        assert!(enemy.skill_usage.is_none() && enemy.reactions_left.is_none());
+    }
+
+    #[test]
+    fn test_round_health() {
+        assert_eq!(GameStateView::round_health(13, 5), 15);
+        assert_eq!(GameStateView::round_health(12, 5), 10);
+        assert_eq!(GameStateView::round_health(13, 1), 13);
+        // never rounded down to 0, nor up from it
+        assert_eq!(GameStateView::round_health(1, 5), 5);
+        assert_eq!(GameStateView::round_health(0, 5), 0);
+        assert_eq!(GameStateView::round_health(-3, 5), -3);
+    }
+}
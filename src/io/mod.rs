@@ -1,11 +1,17 @@
-use crate::game::{Action, Cell, Character, Class, GameMap, GameState, Id, Team};
+use crate::game::{
+    Action, Cell, Character, Class, Error, Event, FogOfWar, GameMap, GameState, GameStateView, Id,
+    LobbyState, PlayerToken, Team,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
-pub struct WireAction(pub Action);
+pub struct WireAction(pub PlayerToken, pub Action);
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WireNewCharRequest {
+    /// Players creating their first character do not have a token yet
+    #[serde(default)]
+    pub token: Option<PlayerToken>,
     pub name: String,
     pub class: Id<Class>,
     pub team: Id<Team>,
@@ -20,17 +26,96 @@ pub struct WireCreatedGame {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-pub struct WireCreatedChar(pub String, pub Id<Character>);
+pub struct WireCreatedChar(pub PlayerToken, pub Id<Character>);
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct WireNewGameRequest {
     pub map: Id<GameMap>,
     pub team_size: usize,
+    /// None means every player sees the whole game
+    #[serde(default)]
+    pub fog: Option<FogOfWar>,
+    /// Meant for local (hotseat) games
+    #[serde(default)]
+    pub allow_undo: bool,
 }
 
+/// Game still waiting for players, as listed in the lobby
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireOpenGame {
+    pub game_id: String,
+    pub map: Id<GameMap>,
+    pub team_size: usize,
+    pub spots_left: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireOpenGames(pub Vec<WireOpenGame>);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireLeaveRequest {
+    pub token: PlayerToken,
+    pub character: Id<Character>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireReadyRequest {
+    pub token: PlayerToken,
+    pub character: Id<Character>,
+    pub ready: bool,
+}
+
+/// Sent by a player after a disconnection to find back their characters
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireReconnect(pub PlayerToken);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireReconnected(pub Vec<Id<Character>>);
+
+/// Sent back whenever a request is refused
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireError(pub Error);
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum WireGetGame {
     Running(GameState),
-    BeingCreated(Id<GameMap>, usize),
+    BeingCreated(LobbyState),
     None,
 }
+
+/// Redacted game state sent to the players of a single team when playing with fog of war
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireGameView(pub GameStateView);
+
+/// What happened during the last action, sent before the resulting game. With fog of war, players
+/// only receive the events their team could see.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WireEvents(pub Vec<Event>);
+
+/// Every message a client can send, the String being the id of the game it concerns
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WireRequest {
+    ListGames,
+    NewGame(WireNewGameRequest),
+    GetGame(String),
+    NewChar(String, WireNewCharRequest),
+    Leave(String, WireLeaveRequest),
+    Ready(String, WireReadyRequest),
+    Reconnect(String, WireReconnect),
+    Action(String, WireAction),
+    Undo(String, PlayerToken),
+    Redo(String, PlayerToken),
+}
+
+/// Every message a client can receive
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WireResponse {
+    OpenGames(WireOpenGames),
+    CreatedGame(WireCreatedGame),
+    CreatedChar(WireCreatedChar),
+    Reconnected(WireReconnected),
+    Game(WireGetGame),
+    GameView(WireGameView),
+    Events(WireEvents),
+    Error(WireError),
+}
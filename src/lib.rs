@@ -1,3 +1,3 @@
-#![feature(vec_remove_item)]
 pub mod game;
 pub mod io;
+pub mod server;
@@ -0,0 +1,721 @@
+use crate::game::{
+    Error, FogOfWar, GameDefinition, GameState, Id, Lobby, PlayerToken, SaveGame, Sessions, Team,
+    UndoHistory,
+};
+use crate::io::{
+    WireAction, WireCreatedChar, WireCreatedGame, WireError, WireEvents, WireGameView, WireGetGame,
+    WireLeaveRequest, WireNewCharRequest, WireNewGameRequest, WireOpenGame, WireOpenGames,
+    WireReadyRequest, WireReconnect, WireReconnected, WireRequest, WireResponse,
+};
+use log::{debug, info};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+const GAME_ID_LENGTH: usize = 8;
+
+/// Identifies a connection, attributed by the transport layer
+pub type ClientId = u64;
+
+/// Messages to send, and to whom
+pub type Outgoing = Vec<(ClientId, WireResponse)>;
+
+#[derive(Debug)]
+enum Phase<'a> {
+    Lobby(Lobby<'a>),
+    Running(GameState, Sessions),
+}
+
+#[derive(Debug)]
+struct HostedGame<'a> {
+    phase: Phase<'a>,
+    team_size: usize,
+    fog: Option<FogOfWar>,
+    /// None if undoing actions is not allowed
+    history: Option<UndoHistory>,
+    /// Clients following the game, along with the token of their player if they have one
+    clients: HashMap<ClientId, Option<PlayerToken>>,
+}
+
+impl<'a> HostedGame<'a> {
+    fn subscribe(&mut self, client: ClientId, token: Option<PlayerToken>) {
+        let subscribed = self.clients.entry(client).or_insert(None);
+        if token.is_some() {
+            *subscribed = token;
+        }
+    }
+
+    fn lobby(&mut self) -> Result<&mut Lobby<'a>, Error> {
+        match &mut self.phase {
+            Phase::Lobby(lobby) => Ok(lobby),
+            Phase::Running(..) => Err(Error::GameAlreadyStarted),
+        }
+    }
+
+    fn sessions(&self) -> &Sessions {
+        match &self.phase {
+            Phase::Lobby(lobby) => lobby.sessions(),
+            Phase::Running(_, sessions) => sessions,
+        }
+    }
+
+    fn team_of(gs: &GameState, sessions: &Sessions, token: &PlayerToken) -> Option<Id<Team>> {
+        sessions
+            .characters_of(token)
+            .ok()?
+            .iter()
+            .find_map(|id| gs.characters.get(*id))
+            .map(|character| character.team)
+    }
+
+    /// With fog of war, only players get to see (their part of) the game
+    fn state_for(&self, g: &GameDefinition, token: Option<&PlayerToken>) -> WireResponse {
+        match (&self.phase, &self.fog) {
+            (Phase::Lobby(lobby), _) => {
+                WireResponse::Game(WireGetGame::BeingCreated(lobby.state()))
+            }
+            (Phase::Running(gs, _), None) => WireResponse::Game(WireGetGame::Running(gs.clone())),
+            (Phase::Running(gs, sessions), Some(fog)) => {
+                match token.and_then(|token| HostedGame::team_of(gs, sessions, token)) {
+                    Some(team) => {
+                        WireResponse::GameView(WireGameView(gs.view_for_team(g, team, fog)))
+                    }
+                    None => WireResponse::Error(WireError(Error::InvalidToken)),
+                }
+            }
+        }
+    }
+
+    /// Clients without a token only follow running games played without fog of war
+    fn broadcast(&self, g: &GameDefinition) -> Outgoing {
+        let spectators_allowed = self.fog.is_none() || matches!(self.phase, Phase::Lobby(_));
+        self.clients
+            .iter()
+            .filter(|(_, token)| token.is_some() || spectators_allowed)
+            .map(|(client, token)| (*client, self.state_for(g, token.as_ref())))
+            .collect()
+    }
+
+    /// Same recipients as the game itself, token-less clients not seeing anything with fog of war
+    fn broadcast_events(&self, g: &GameDefinition) -> Outgoing {
+        let (gs, sessions) = match &self.phase {
+            Phase::Running(gs, sessions) => (gs, sessions),
+            Phase::Lobby(_) => return Vec::new(),
+        };
+        self.clients
+            .iter()
+            .filter_map(|(client, token)| {
+                let events = match &self.fog {
+                    None => gs.events().to_vec(),
+                    Some(fog) => {
+                        let team = HostedGame::team_of(gs, sessions, token.as_ref()?)?;
+                        gs.view_for_team(g, team, fog).visible_events(gs.events())
+                    }
+                };
+                Some((*client, WireResponse::Events(WireEvents(events))))
+            })
+            .collect()
+    }
+
+    /// Returns true if the game just started. The lobby is only consumed once it is known to be
+    /// complete, so that the game is never left without a phase.
+    fn try_start(&mut self) -> bool {
+        let started = match &self.phase {
+            Phase::Lobby(lobby) if lobby.can_start() => lobby.clone().try_start().ok(),
+            _ => None,
+        };
+        match started {
+            Some((gs, sessions)) => {
+                self.phase = Phase::Running(gs, sessions);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn new_char(
+        &mut self,
+        g: &GameDefinition,
+        client: ClientId,
+        request: WireNewCharRequest,
+    ) -> Result<Outgoing, Error> {
+        let (id, token) = self.lobby()?.join(
+            request.token.as_ref(),
+            request.name,
+            request.class,
+            request.team,
+            request.position,
+        )?;
+        self.subscribe(client, Some(token.clone()));
+
+        let mut outgoing = vec![(
+            client,
+            WireResponse::CreatedChar(WireCreatedChar(token, id)),
+        )];
+        outgoing.extend(self.broadcast(g));
+        Ok(outgoing)
+    }
+
+    fn leave(&mut self, g: &GameDefinition, request: WireLeaveRequest) -> Result<Outgoing, Error> {
+        self.lobby()?.leave(&request.token, request.character)?;
+        Ok(self.broadcast(g))
+    }
+
+    fn ready(&mut self, g: &GameDefinition, request: WireReadyRequest) -> Result<Outgoing, Error> {
+        self.lobby()?
+            .set_ready(&request.token, request.character, request.ready)?;
+        Ok(self.broadcast(g))
+    }
+
+    fn reconnect(
+        &mut self,
+        g: &GameDefinition,
+        client: ClientId,
+        request: WireReconnect,
+    ) -> Result<Outgoing, Error> {
+        let WireReconnect(token) = request;
+        let characters = self.sessions().characters_of(&token)?;
+        self.subscribe(client, Some(token.clone()));
+
+        Ok(vec![
+            (
+                client,
+                WireResponse::Reconnected(WireReconnected(characters)),
+            ),
+            (client, self.state_for(g, Some(&token))),
+        ])
+    }
+
+    fn action(&mut self, g: &GameDefinition, request: WireAction) -> Result<Outgoing, Error> {
+        let WireAction(token, action) = request;
+        match &mut self.phase {
+            Phase::Lobby(_) => return Err(Error::GameNotStarted),
+            Phase::Running(gs, sessions) => {
+                sessions.check_action(&token, gs)?;
+                match &mut self.history {
+                    Some(history) => history.next_action(gs, g, action)?,
+                    None => gs.next_action(g, action)?,
+                };
+            }
+        }
+        let mut outgoing = self.broadcast_events(g);
+        outgoing.extend(self.broadcast(g));
+        Ok(outgoing)
+    }
+
+    /// Only the player who played the undone action may undo it
+    fn undo(&mut self, g: &GameDefinition, token: PlayerToken) -> Result<Outgoing, Error> {
+        let history = self.history.as_mut().ok_or(Error::UndoDisabled)?;
+        match &mut self.phase {
+            Phase::Lobby(_) => return Err(Error::GameNotStarted),
+            Phase::Running(gs, sessions) => {
+                let previous = history.peek_undo().ok_or(Error::NothingToUndo)?;
+                sessions.check_action(&token, previous)?;
+                history.undo(gs)?;
+            }
+        }
+        Ok(self.broadcast(g))
+    }
+
+    fn redo(&mut self, g: &GameDefinition, token: PlayerToken) -> Result<Outgoing, Error> {
+        let history = self.history.as_mut().ok_or(Error::UndoDisabled)?;
+        match &mut self.phase {
+            Phase::Lobby(_) => return Err(Error::GameNotStarted),
+            Phase::Running(gs, sessions) => {
+                sessions.check_action(&token, gs)?;
+                history.redo(gs)?;
+            }
+        }
+        Ok(self.broadcast(g))
+    }
+}
+
+/// Hosts any number of games, be they still in their lobby or running. It is not tied to any
+/// transport: the network layer simply forwards the requests of its clients, and sends back the
+/// outgoing messages.
+#[derive(Debug)]
+pub struct GameServer<'a> {
+    definition: &'a GameDefinition,
+    games: Mutex<HashMap<String, HostedGame<'a>>>,
+}
+
+impl<'a> GameServer<'a> {
+    pub fn new(definition: &'a GameDefinition) -> Self {
+        GameServer {
+            definition,
+            games: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn definition(&self) -> &'a GameDefinition {
+        self.definition
+    }
+
+    /// A request panicking leaves its game as it was at the time, which must not prevent the
+    /// other games from being played
+    fn games(&self) -> MutexGuard<'_, HashMap<String, HostedGame<'a>>> {
+        self.games.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn handle(&self, client: ClientId, request: WireRequest) -> Outgoing {
+        let mut games = self.games();
+        let g = self.definition;
+
+        let result = match request {
+            WireRequest::ListGames => Ok(vec![(client, GameServer::open_games(&games))]),
+            WireRequest::NewGame(request) => GameServer::new_game(&mut games, g, client, request),
+            WireRequest::GetGame(game_id) => games
+                .get_mut(&game_id)
+                .ok_or(Error::InvalidGameId)
+                .map(|game| {
+                    game.subscribe(client, None);
+                    let token = game.clients[&client].clone();
+                    vec![(client, game.state_for(g, token.as_ref()))]
+                }),
+            WireRequest::NewChar(game_id, request) => {
+                GameServer::with_game(&mut games, g, game_id, |game| {
+                    game.new_char(g, client, request)
+                })
+            }
+            WireRequest::Leave(game_id, request) => {
+                GameServer::with_game(&mut games, g, game_id, |game| game.leave(g, request))
+            }
+            WireRequest::Ready(game_id, request) => {
+                GameServer::with_game(&mut games, g, game_id, |game| game.ready(g, request))
+            }
+            WireRequest::Reconnect(game_id, request) => {
+                GameServer::with_game(&mut games, g, game_id, |game| {
+                    game.reconnect(g, client, request)
+                })
+            }
+            WireRequest::Action(game_id, request) => {
+                GameServer::with_game(&mut games, g, game_id, |game| game.action(g, request))
+            }
+            WireRequest::Undo(game_id, token) => {
+                GameServer::with_game(&mut games, g, game_id, |game| game.undo(g, token))
+            }
+            WireRequest::Redo(game_id, token) => {
+                GameServer::with_game(&mut games, g, game_id, |game| game.redo(g, token))
+            }
+        };
+
+        result.unwrap_or_else(|e| {
+            debug!("Request from client {} refused: {}", client, e);
+            vec![(client, WireResponse::Error(WireError(e)))]
+        })
+    }
+
+    /// Stops sending updates to the given client
+    pub fn disconnect(&self, client: ClientId) {
+        let mut games = self.games();
+        for game in games.values_mut() {
+            game.clients.remove(&client);
+        }
+    }
+
+    /// Only running games can be saved, lobbies being short-lived
+    pub fn save(&self, game_id: &str) -> Result<SaveGame, Error> {
+        let games = self.games();
+        let game = games.get(game_id).ok_or(Error::InvalidGameId)?;
+        match &game.phase {
+            Phase::Lobby(_) => Err(Error::GameNotStarted),
+            Phase::Running(gs, sessions) => Ok(SaveGame::new(
+                self.definition,
+                game_id,
+                game.team_size,
+                game.fog.clone(),
+                game.history.is_some(),
+                sessions.clone(),
+                gs,
+            )),
+        }
+    }
+
+    /// Hosts a saved game again, its players having to reconnect with their tokens
+    pub fn restore(&self, save: SaveGame) -> Result<(), Error> {
+        let mut games = self.games();
+        if games.contains_key(&save.game_id) {
+            return Err(Error::GameIdTaken);
+        }
+
+        let game_id = save.game_id.clone();
+        let team_size = save.team_size;
+        let fog = save.fog.clone();
+        let history = if save.allow_undo {
+            Some(UndoHistory::new())
+        } else {
+            None
+        };
+        let (gs, sessions) = save.restore(self.definition)?;
+        info!("Game {} restored", game_id);
+        games.insert(
+            game_id,
+            HostedGame {
+                phase: Phase::Running(gs, sessions),
+                team_size,
+                fog,
+                history,
+                clients: HashMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    fn open_games(games: &HashMap<String, HostedGame<'a>>) -> WireResponse {
+        let mut open_games = games
+            .iter()
+            .filter_map(|(game_id, game)| match &game.phase {
+                Phase::Lobby(lobby) if lobby.spots_left() > 0 => Some(WireOpenGame {
+                    game_id: game_id.clone(),
+                    map: lobby.map(),
+                    team_size: lobby.team_size(),
+                    spots_left: lobby.spots_left(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        open_games.sort_by(|a, b| a.game_id.cmp(&b.game_id));
+        WireResponse::OpenGames(WireOpenGames(open_games))
+    }
+
+    fn new_game(
+        games: &mut HashMap<String, HostedGame<'a>>,
+        g: &'a GameDefinition,
+        client: ClientId,
+        request: WireNewGameRequest,
+    ) -> Result<Outgoing, Error> {
+        // undoing a move would let its player see what they revealed, and go somewhere else
+        if request.allow_undo && request.fog.is_some() {
+            return Err(Error::UndoWithFog);
+        }
+        let lobby = Lobby::new(g, request.map, request.team_size)?;
+
+        let game_id = loop {
+            let game_id = thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(GAME_ID_LENGTH)
+                .collect::<String>();
+            if !games.contains_key(&game_id) {
+                break game_id;
+            }
+        };
+        info!("New game {} created by client {}", game_id, client);
+
+        let mut clients = HashMap::new();
+        clients.insert(client, None);
+        games.insert(
+            game_id.clone(),
+            HostedGame {
+                phase: Phase::Lobby(lobby),
+                team_size: request.team_size,
+                fog: request.fog,
+                history: if request.allow_undo {
+                    Some(UndoHistory::new())
+                } else {
+                    None
+                },
+                clients,
+            },
+        );
+
+        Ok(vec![(
+            client,
+            WireResponse::CreatedGame(WireCreatedGame {
+                game_id,
+                map: request.map,
+                team_size: request.team_size,
+            }),
+        )])
+    }
+
+    /// Runs a request against a game, then starts it if the lobby is complete
+    fn with_game<F>(
+        games: &mut HashMap<String, HostedGame<'a>>,
+        g: &GameDefinition,
+        game_id: String,
+        f: F,
+    ) -> Result<Outgoing, Error>
+    where
+        F: FnOnce(&mut HostedGame<'a>) -> Result<Outgoing, Error>,
+    {
+        let game = games.get_mut(&game_id).ok_or(Error::InvalidGameId)?;
+        let result = f(game);
+
+        let started = game.try_start();
+        result.map(|mut outgoing| {
+            if started {
+                info!("Game {} started", game_id);
+                outgoing.extend(game.broadcast(g));
+            }
+            outgoing
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClientId, GameServer, Outgoing};
+    use crate::game::test_utils::game_definition;
+    use crate::game::{Action, Character, Error, FogOfWar, GameState, Id, PlayerToken};
+    use crate::io::{
+        WireAction, WireCreatedChar, WireError, WireGetGame, WireNewCharRequest,
+        WireNewGameRequest, WireReadyRequest, WireRequest, WireResponse,
+    };
+    use serde_json::json;
+
+    fn join(
+        server: &GameServer,
+        client: ClientId,
+        game_id: &str,
+        team: usize,
+        position: usize,
+    ) -> (Id<Character>, PlayerToken) {
+        let outgoing = server.handle(
+            client,
+            WireRequest::NewChar(
+                game_id.to_owned(),
+                WireNewCharRequest {
+                    token: None,
+                    name: format!("player {}", client),
+                    class: Id::new(0),
+                    team: Id::new(team),
+                    position: Id::new(position),
+                },
+            ),
+        );
+        match outgoing.into_iter().next() {
+            Some((_, WireResponse::CreatedChar(WireCreatedChar(token, id)))) => (id, token),
+            other => panic!("Unexpected response {:?}", other),
+        }
+    }
+
+    /// Created by client 0
+    fn create(server: &GameServer, fog: Option<FogOfWar>, allow_undo: bool) -> String {
+        match server.handle(
+            0,
+            WireRequest::NewGame(WireNewGameRequest {
+                map: Id::new(0),
+                team_size: 1,
+                fog,
+                allow_undo,
+            }),
+        )[0]
+        .1
+        {
+            WireResponse::CreatedGame(ref created) => created.game_id.clone(),
+            ref other => panic!("Unexpected response {:?}", other),
+        }
+    }
+
+    fn ready(
+        server: &GameServer,
+        client: ClientId,
+        game_id: &str,
+        character: Id<Character>,
+        token: &PlayerToken,
+    ) -> Outgoing {
+        server.handle(
+            client,
+            WireRequest::Ready(
+                game_id.to_owned(),
+                WireReadyRequest {
+                    token: token.clone(),
+                    character,
+                    ready: true,
+                },
+            ),
+        )
+    }
+
+    fn running_game(outgoing: &Outgoing) -> &GameState {
+        outgoing
+            .iter()
+            .find_map(|(_, response)| match response {
+                WireResponse::Game(WireGetGame::Running(gs)) => Some(gs),
+                _ => None,
+            })
+            .expect("Game is not running")
+    }
+
+    fn pass(server: &GameServer, client: ClientId, game_id: &str, token: &PlayerToken) -> Outgoing {
+        server.handle(
+            client,
+            WireRequest::Action(game_id.to_owned(), WireAction(token.clone(), Action::Pass)),
+        )
+    }
+
+    fn running_for(outgoing: &Outgoing, client: ClientId) -> bool {
+        outgoing.iter().any(|(to, response)| {
+            *to == client && matches!(response, WireResponse::Game(WireGetGame::Running(_)))
+        })
+    }
+
+    #[test]
+    fn test_server_is_sync() {
+        fn assert_sync<T: Sync + Send>() {}
+        assert_sync::<GameServer>();
+    }
+
+    #[test]
+    fn test_server_game() {
+        let g = game_definition();
+        let server = GameServer::new(&g);
+
+        let game_id = create(&server, None, false);
+
+        let (first, first_token) = join(&server, 1, &game_id, 0, 0);
+        let (second, second_token) = join(&server, 2, &game_id, 1, 4);
+
+        let outgoing = ready(&server, 1, &game_id, first, &first_token);
+        assert!(!running_for(&outgoing, 1));
+
+        let outgoing = ready(&server, 2, &game_id, second, &second_token);
+        assert!(running_for(&outgoing, 0));
+        assert!(running_for(&outgoing, 1));
+        assert!(running_for(&outgoing, 2));
+
+        let (owner, other) = if running_game(&outgoing).player_to_play() == first {
+            ((1, first_token), (2, second_token))
+        } else {
+            ((2, second_token), (1, first_token))
+        };
+
+        let outgoing = pass(&server, other.0, &game_id, &other.1);
+        assert!(matches!(
+            outgoing.as_slice(),
+            [(_, WireResponse::Error(WireError(Error::NotCharacterOwner)))]
+        ));
+
+        let outgoing = pass(&server, owner.0, &game_id, &owner.1);
+        assert!(running_for(&outgoing, 1));
+        assert!(running_for(&outgoing, 2));
+        for client in &[1, 2] {
+            assert!(outgoing.iter().any(|(to, response)| {
+                to == client && matches!(response, WireResponse::Events(_))
+            }));
+        }
+    }
+
+    #[test]
+    fn test_server_save() {
+        let g = game_definition();
+        let server = GameServer::new(&g);
+        let game_id = create(&server, None, true);
+        let (first, first_token) = join(&server, 1, &game_id, 0, 0);
+        let (second, second_token) = join(&server, 2, &game_id, 1, 4);
+        ready(&server, 1, &game_id, first, &first_token);
+        let outgoing = ready(&server, 2, &game_id, second, &second_token);
+        let (client, token) = if running_game(&outgoing).player_to_play() == first {
+            (1, first_token)
+        } else {
+            (2, second_token)
+        };
+
+        let save = server.save(&game_id).unwrap();
+        assert!(save.allow_undo);
+        let server = GameServer::new(&g);
+        server.restore(save).unwrap();
+
+        // the restored game starts with an empty history
+        let undo = || server.handle(client, WireRequest::Undo(game_id.clone(), token.clone()));
+        assert!(matches!(
+            undo().as_slice(),
+            [(_, WireResponse::Error(WireError(Error::NothingToUndo)))]
+        ));
+        pass(&server, client, &game_id, &token);
+        assert!(!undo()
+            .iter()
+            .any(|(_, response)| matches!(response, WireResponse::Error(_))));
+    }
+
+    #[test]
+    fn test_server_summon() {
+        let mut g = game_definition();
+        g.effects.get_mut(Id::new(0)).unwrap().kind = serde_json::from_value(json!({
+            "Summon": { "class": 0, "name": "Totem", "lifespan": null }
+        }))
+        .unwrap();
+        g.skills.get_mut(Id::new(0)).unwrap().range.target =
+            serde_json::from_value(json!("Anything")).unwrap();
+        let server = GameServer::new(&g);
+
+        let game_id = create(&server, None, false);
+        let (first, first_token) = join(&server, 1, &game_id, 0, 0);
+        let (second, second_token) = join(&server, 2, &game_id, 1, 4);
+        ready(&server, 1, &game_id, first, &first_token);
+        let outgoing = ready(&server, 2, &game_id, second, &second_token);
+
+        let gs = running_game(&outgoing);
+        let summoner = gs.player_to_play();
+        let (owner, other) = if summoner == first {
+            ((1, first_token), (2, second_token))
+        } else {
+            ((2, second_token), (1, first_token))
+        };
+        // right below the summoner
+        let cell = Id::new(gs.characters.get(summoner).unwrap().position.raw() + 5);
+        let outgoing = server.handle(
+            owner.0,
+            WireRequest::Action(
+                game_id.clone(),
+                WireAction(owner.1.clone(), Action::Skill(Id::new(0), cell)),
+            ),
+        );
+        let totem = running_game(&outgoing).next_turn_order[0];
+        pass(&server, other.0, &game_id, &other.1);
+
+        // the totem is played by the owner of its summoner
+        let outgoing = pass(&server, other.0, &game_id, &other.1);
+        assert!(matches!(
+            outgoing.as_slice(),
+            [(_, WireResponse::Error(WireError(Error::NotCharacterOwner)))]
+        ));
+        let outgoing = pass(&server, owner.0, &game_id, &owner.1);
+        let gs = running_game(&outgoing);
+        assert!(gs.characters.get(totem).is_some());
+        assert_ne!(gs.player_to_play(), totem);
+    }
+
+    #[test]
+    fn test_server_fog() {
+        let g = game_definition();
+        let server = GameServer::new(&g);
+        let outgoing = server.handle(
+            0,
+            WireRequest::NewGame(WireNewGameRequest {
+                map: Id::new(0),
+                team_size: 1,
+                fog: Some(FogOfWar::default()),
+                allow_undo: true,
+            }),
+        );
+        assert!(matches!(
+            outgoing.as_slice(),
+            [(0, WireResponse::Error(WireError(Error::UndoWithFog)))]
+        ));
+
+        let game_id = create(&server, Some(FogOfWar::default()), false);
+
+        let (first, first_token) = join(&server, 1, &game_id, 0, 0);
+        let (second, second_token) = join(&server, 2, &game_id, 1, 4);
+        ready(&server, 1, &game_id, first, &first_token);
+        let outgoing = ready(&server, 2, &game_id, second, &second_token);
+
+        // the creator has no character, hence no team whose view they could get: they only see
+        // the lobby as it was before the game started
+        assert!(outgoing.iter().all(|(client, response)| *client != 0
+            || matches!(response, WireResponse::Game(WireGetGame::BeingCreated(_)))));
+        for client in &[1, 2] {
+            assert!(outgoing.iter().any(|(to, response)| {
+                to == client && matches!(response, WireResponse::GameView(_))
+            }));
+        }
+        assert!(!outgoing
+            .iter()
+            .any(|(_, response)| matches!(response, WireResponse::Error(_))));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::game::Error;
+use crate::io::{WireError, WireRequest, WireResponse};
+use crate::server::{ClientId, GameServer, Outgoing};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{accept, Message, WebSocket};
+
+/// How long a connection waits for a request before sending its pending messages
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Outgoing queues of every connected client
+#[derive(Default)]
+struct Connections {
+    next_id: AtomicU64,
+    senders: Mutex<HashMap<ClientId, Sender<WireResponse>>>,
+}
+
+impl Connections {
+    fn register(&self) -> (ClientId, Receiver<WireResponse>) {
+        let client = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = channel();
+        self.senders
+            .lock()
+            .expect("Connection list mutex was poisoned")
+            .insert(client, sender);
+        (client, receiver)
+    }
+
+    fn unregister(&self, client: ClientId) {
+        self.senders
+            .lock()
+            .expect("Connection list mutex was poisoned")
+            .remove(&client);
+    }
+
+    fn dispatch(&self, outgoing: Outgoing) {
+        let senders = self
+            .senders
+            .lock()
+            .expect("Connection list mutex was poisoned");
+        for (client, response) in outgoing {
+            // the client may have disconnected in the meantime, which is fine
+            if let Some(sender) = senders.get(&client) {
+                let _ = sender.send(response);
+            }
+        }
+    }
+}
+
+/// Accepts WebSocket connections until the listener fails, each connection being handled by its
+/// own thread. Every text message must be a JSON serialized WireRequest, and every message sent
+/// back is a JSON serialized WireResponse.
+pub fn serve(listener: TcpListener, server: Arc<GameServer<'static>>) {
+    let connections = Arc::new(Connections::default());
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Could not accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let server = Arc::clone(&server);
+        let connections = Arc::clone(&connections);
+        thread::spawn(move || handle_connection(stream, &server, &connections));
+    }
+}
+
+fn handle_connection(stream: TcpStream, server: &GameServer, connections: &Connections) {
+    let peer = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    let mut websocket = match accept(stream) {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            warn!("WebSocket handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+
+    if let Err(e) = websocket.get_ref().set_read_timeout(Some(POLL_INTERVAL)) {
+        warn!("Could not configure connection with {}: {}", peer, e);
+        return;
+    }
+
+    let (client, receiver) = connections.register();
+    info!("Client {} connected from {}", client, peer);
+
+    while run_once(&mut websocket, client, &receiver, server, connections) {}
+
+    server.disconnect(client);
+    connections.unregister(client);
+    info!("Client {} disconnected", client);
+}
+
+/// Returns false once the connection is closed
+fn run_once(
+    websocket: &mut WebSocket<TcpStream>,
+    client: ClientId,
+    receiver: &Receiver<WireResponse>,
+    server: &GameServer,
+    connections: &Connections,
+) -> bool {
+    match websocket.read_message() {
+        Ok(Message::Text(text)) => {
+            let outgoing = match serde_json::from_str::<WireRequest>(&text) {
+                Ok(request) => server.handle(client, request),
+                Err(e) => {
+                    debug!("Invalid request from client {}: {}", client, e);
+                    vec![(
+                        client,
+                        WireResponse::Error(WireError(Error::InvalidRequest)),
+                    )]
+                }
+            };
+            connections.dispatch(outgoing);
+        }
+        Ok(_) => {}
+        Err(tungstenite::Error::Io(ref e))
+            if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+        Err(e) => {
+            debug!("Connection with client {} closed: {}", client, e);
+            return false;
+        }
+    }
+
+    for response in receiver.try_iter() {
+        let text = serde_json::to_string(&response).expect("Could not serialize response");
+        if let Err(e) = websocket.write_message(Message::Text(text)) {
+            debug!("Could not send message to client {}: {}", client, e);
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,222 @@
+#![cfg(feature = "websocket")]
+use pewcraft_common::game::{Action, Character, GameDefinition, GameState, Id, PlayerToken};
+use pewcraft_common::io::{
+    WireAction, WireCreatedChar, WireGetGame, WireNewCharRequest, WireNewGameRequest,
+    WireReadyRequest, WireRequest, WireResponse,
+};
+use pewcraft_common::server::{websocket, GameServer};
+use serde_json::json;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tungstenite::{client, Message, WebSocket};
+
+fn game_definition() -> GameDefinition {
+    let cell = json!({ "height": 0, "attribute": "None" });
+    serde_json::from_value(json!({
+        "classes": {
+            "0": {
+                "name": "Warrior",
+                "description": "Hits things",
+                "health": 20,
+                "mana": 0,
+                "swiftness": 3,
+                "strength": 5,
+                "dexterity": 5,
+                "armor": 1,
+                "intelligence": 0,
+                "concentration": 0,
+                "willpower": 0
+            }
+        },
+        "skills": {},
+        "effects": {},
+        "maps": {
+            "0": {
+                "name": "Arena",
+                "data": vec![cell; 4],
+                "width": 2,
+                "height": 2,
+                "teams": [["Left", [0]], ["Right", [1]]]
+            }
+        },
+        "class_to_skills": {},
+        "skill_to_classes": {}
+    }))
+    .unwrap()
+}
+
+fn start_server() -> String {
+    let definition: &'static GameDefinition = Box::leak(Box::new(game_definition()));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = Arc::new(GameServer::new(definition));
+    thread::spawn(move || websocket::serve(listener, server));
+    address
+}
+
+fn connect(address: &str) -> WebSocket<TcpStream> {
+    let stream = TcpStream::connect(address).unwrap();
+    client(format!("ws://{}/", address), stream).unwrap().0
+}
+
+fn send(websocket: &mut WebSocket<TcpStream>, request: &WireRequest) {
+    let text = serde_json::to_string(request).unwrap();
+    websocket.write_message(Message::Text(text)).unwrap();
+}
+
+fn receive(websocket: &mut WebSocket<TcpStream>) -> WireResponse {
+    loop {
+        if let Message::Text(text) = websocket.read_message().unwrap() {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+fn request(websocket: &mut WebSocket<TcpStream>, request: &WireRequest) -> WireResponse {
+    send(websocket, request);
+    receive(websocket)
+}
+
+/// Skips the other messages (e.g. lobby updates) until the game state is broadcast
+fn receive_running(websocket: &mut WebSocket<TcpStream>) -> GameState {
+    loop {
+        if let WireResponse::Game(WireGetGame::Running(gs)) = receive(websocket) {
+            return gs;
+        }
+    }
+}
+
+fn join(
+    websocket: &mut WebSocket<TcpStream>,
+    game_id: &str,
+    team: usize,
+    position: usize,
+) -> (Id<Character>, PlayerToken) {
+    let request = WireRequest::NewChar(
+        game_id.to_owned(),
+        WireNewCharRequest {
+            token: None,
+            name: format!("player {}", team),
+            class: Id::new(0),
+            team: Id::new(team),
+            position: Id::new(position),
+        },
+    );
+    send(websocket, &request);
+    loop {
+        if let WireResponse::CreatedChar(WireCreatedChar(token, id)) = receive(websocket) {
+            return (id, token);
+        }
+    }
+}
+
+fn ready(
+    websocket: &mut WebSocket<TcpStream>,
+    game_id: &str,
+    character: Id<Character>,
+    token: &PlayerToken,
+) {
+    let request = WireRequest::Ready(
+        game_id.to_owned(),
+        WireReadyRequest {
+            token: token.clone(),
+            character,
+            ready: true,
+        },
+    );
+    send(websocket, &request);
+}
+
+#[test]
+fn test_create_and_list_games() {
+    let address = start_server();
+    let mut host = connect(&address);
+    let mut guest = connect(&address);
+
+    let created = request(
+        &mut host,
+        &WireRequest::NewGame(WireNewGameRequest {
+            map: Id::new(0),
+            team_size: 1,
+            fog: None,
+            allow_undo: false,
+        }),
+    );
+    let game_id = match created {
+        WireResponse::CreatedGame(created) => created.game_id,
+        other => panic!("Unexpected response {:?}", other),
+    };
+
+    match request(&mut guest, &WireRequest::ListGames) {
+        WireResponse::OpenGames(games) => {
+            assert_eq!(games.0.len(), 1);
+            assert_eq!(games.0[0].game_id, game_id);
+            assert_eq!(games.0[0].spots_left, 2);
+        }
+        other => panic!("Unexpected response {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_request() {
+    let address = start_server();
+    let mut websocket = connect(&address);
+
+    websocket
+        .write_message(Message::Text("not a request".to_owned()))
+        .unwrap();
+    let response = loop {
+        if let Message::Text(text) = websocket.read_message().unwrap() {
+            break serde_json::from_str::<WireResponse>(&text).unwrap();
+        }
+    };
+    assert!(matches!(response, WireResponse::Error(_)));
+}
+
+#[test]
+fn test_play_game() {
+    let address = start_server();
+    let mut host = connect(&address);
+    let mut guest = connect(&address);
+    // a missing broadcast fails the test instead of blocking it
+    for websocket in &[&host, &guest] {
+        let timeout = Some(Duration::from_secs(5));
+        websocket.get_ref().set_read_timeout(timeout).unwrap();
+    }
+
+    let created = request(
+        &mut host,
+        &WireRequest::NewGame(WireNewGameRequest {
+            map: Id::new(0),
+            team_size: 1,
+            fog: None,
+            allow_undo: false,
+        }),
+    );
+    let game_id = match created {
+        WireResponse::CreatedGame(created) => created.game_id,
+        other => panic!("Unexpected response {:?}", other),
+    };
+
+    let (host_char, host_token) = join(&mut host, &game_id, 0, 0);
+    let (guest_char, guest_token) = join(&mut guest, &game_id, 1, 1);
+    ready(&mut host, &game_id, host_char, &host_token);
+    ready(&mut guest, &game_id, guest_char, &guest_token);
+
+    let gs = receive_running(&mut host);
+    assert_eq!(receive_running(&mut guest).turn_order, gs.turn_order);
+
+    let (playing, token, waiting) = if gs.player_to_play() == host_char {
+        (&mut host, host_token, guest_char)
+    } else {
+        (&mut guest, guest_token, host_char)
+    };
+    let action = WireRequest::Action(game_id, WireAction(token, Action::Pass));
+    send(playing, &action);
+
+    // both players see the turn of the other character start
+    assert_eq!(receive_running(&mut host).player_to_play(), waiting);
+    assert_eq!(receive_running(&mut guest).player_to_play(), waiting);
+}